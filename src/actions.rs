@@ -6,6 +6,7 @@ use std::collections::VecDeque;
 pub struct ActionList {
     undo_list: Vec<Box<Action>>,
     redo_list: VecDeque<Box<Action>>,
+    changes: u64,
 }
 
 pub trait Action {
@@ -47,6 +48,27 @@ pub struct SortDirectionAction {
     old_sort_descending: bool,
 }
 
+pub struct RestoreAction {
+    old_root: notes::Entry,
+    old_next_id: u32,
+    new_root: notes::Entry,
+    new_next_id: u32,
+}
+
+pub struct AddTagAction {
+    id: u32,
+    tag: String,
+}
+
+pub struct RemoveTagAction {
+    id: u32,
+    tag: String,
+}
+
+pub struct GroupAction {
+    actions: Vec<Box<Action>>,
+}
+
 pub struct MoveAction {
     id: u32,
     old_category_id: u32,
@@ -58,20 +80,29 @@ impl ActionList {
         ActionList {
             undo_list: Vec::new(),
             redo_list: VecDeque::new(),
+            changes: 0,
         } 
     }
 
     pub fn add(&mut self, action: Box<Action>) {
         self.undo_list.push(action); 
         self.redo_list.clear();
+        self.changes += 1;
     }
 
     pub fn add_redo(&mut self, action: Box<Action>) {
         self.redo_list.push_back(action); 
+        self.changes += 1;
     }
 
     pub fn add_undo(&mut self, action: Box<Action>) {
         self.undo_list.push(action); 
+        self.changes += 1;
+    }
+
+    //Counts every action activated or undone, used to tell if the notebook has unsaved changes
+    pub fn get_changes(&self) -> u64 {
+        self.changes
     }
 
     //Make these options to allow for error handling
@@ -95,6 +126,7 @@ impl DeleteAction {
 impl Action for DeleteAction {
     fn activate(&self, nm: &mut note_manager::NoteManager) {
         nm.root.get_mut_child_by_id(self.deleted_id).unwrap().delete();
+        nm.update_index(self.deleted_id);
 
         if nm.cursor >= nm.root.flatten_children().len() as i32 && nm.cursor > 0 {
             nm.cursor = (nm.root.flatten_children().len() as i32) - 1;
@@ -105,6 +137,7 @@ impl Action for DeleteAction {
 
     fn undo(&self, nm: &mut note_manager::NoteManager) {
         nm.root.get_mut_child_by_id(self.deleted_id).unwrap().undo_delete();
+        nm.update_index(self.deleted_id);
         nm.render();
     }
 }
@@ -122,17 +155,22 @@ impl AddAction {
 impl Action for AddAction {
     fn activate(&self, nm: &mut note_manager::NoteManager) {
         if nm.root.get_child_by_id(self.child.get_id()).is_none() {
-            nm.root.get_mut_child_by_id(self.entry_id).unwrap().add_child(self.child.clone());
-            nm.next_id += 1;
+            let mut child = self.child.clone();
+            child.set_indent(nm.get_indent());
+
+            nm.root.get_mut_child_by_id(self.entry_id).unwrap().add_child(child);
+            nm.next_id = nm.next_id.max(self.child.get_max_id() + 1);
         } else {
             nm.root.get_mut_child_by_id(self.child.get_id()).unwrap().undo_delete(); 
         }
         
+        nm.update_index(self.added_id);
         nm.render();
     } 
 
     fn undo(&self, nm: &mut note_manager::NoteManager) {
         nm.root.get_mut_child_by_id(self.added_id).unwrap().delete(); 
+        nm.update_index(self.added_id);
         nm.render();
     }
 }
@@ -150,11 +188,13 @@ impl RenameAction {
 impl Action for RenameAction {
     fn activate(&self, nm: &mut note_manager::NoteManager) {
         nm.root.get_mut_child_by_id(self.renamed_id).unwrap().change_name(self.new_name.as_str()); 
+        nm.mark_changed(self.renamed_id);
         nm.render();
     }
 
     fn undo(&self, nm: &mut note_manager::NoteManager) {
         nm.root.get_mut_child_by_id(self.renamed_id).unwrap().change_name(self.old_name.as_str()); 
+        nm.mark_changed(self.renamed_id);
         nm.render();
     }
 }
@@ -173,11 +213,17 @@ impl Action for ChangeFileAction {
     fn activate(&self, nm: &mut note_manager::NoteManager) {
         let new_file = notes::build_full_file_path(self.new_location.clone(), nm, nm.root.get_parent_by_id(self.changed_id).unwrap().get_id(), nm.root.get_child_by_id(self.changed_id).unwrap().can_add_child());
         nm.root.get_mut_child_by_id(self.changed_id).unwrap().file_location = new_file;
+        nm.root.get_child_by_id(self.changed_id).unwrap().refresh_missing();
+        nm.mark_changed(self.changed_id);
+        nm.update_index(self.changed_id);
         nm.render();
     }
 
     fn undo(&self, nm: &mut note_manager::NoteManager) {
         nm.root.get_mut_child_by_id(self.changed_id).unwrap().file_location = self.old_location.clone();
+        nm.root.get_child_by_id(self.changed_id).unwrap().refresh_missing();
+        nm.mark_changed(self.changed_id);
+        nm.update_index(self.changed_id);
         nm.render();
     }
 }
@@ -200,6 +246,7 @@ impl Action for SortAction {
             category.sort_children();
         }
         
+        nm.mark_changed(self.id);
         nm.render();
     }
 
@@ -208,6 +255,7 @@ impl Action for SortAction {
             let category = nm.root.get_mut_child_by_id(self.id).unwrap();
             category.set_sort_type(self.old_sort_type.clone());
         }
+        nm.mark_changed(self.id);
         nm.render(); 
     }
 }
@@ -230,6 +278,7 @@ impl Action for SortDirectionAction {
             category.sort_children();
         } 
 
+        nm.mark_changed(self.id);
         nm.render();
     }
 
@@ -239,6 +288,7 @@ impl Action for SortDirectionAction {
             category.set_sort_descending(self.old_sort_descending);
         } 
 
+        nm.mark_changed(self.id);
         nm.render();
     }
 }
@@ -268,6 +318,7 @@ impl Action for MoveAction {
             nm.cursor = (nm.root.flatten_children().len() - 1) as i32; 
         }
 
+        nm.update_index(self.id);
         nm.render();
     }
 
@@ -281,6 +332,110 @@ impl Action for MoveAction {
             }
         }
 
+        nm.update_index(self.id);
         nm.render();
     }
 } 
+
+impl RestoreAction {
+    pub fn new(nm: &note_manager::NoteManager, new_root: notes::Entry, new_next_id: u32) -> RestoreAction {
+        RestoreAction {
+            old_root: nm.root.clone(),
+            old_next_id: nm.next_id,
+            new_root: new_root,
+            new_next_id: new_next_id,
+        } 
+    }
+}
+
+impl Action for RestoreAction {
+    fn activate(&self, nm: &mut note_manager::NoteManager) {
+        nm.set_notebook(self.new_root.clone(), self.new_next_id);
+        nm.mark_notebook_changed();
+        nm.update_index(nm.root.get_id());
+        nm.render();
+    }
+
+    fn undo(&self, nm: &mut note_manager::NoteManager) {
+        nm.set_notebook(self.old_root.clone(), self.old_next_id);
+        nm.mark_notebook_changed();
+        nm.update_index(nm.root.get_id());
+        nm.render();
+    }
+}
+
+impl AddTagAction {
+    pub fn new(entry: &notes::Entry, tag: &str) -> AddTagAction {
+        AddTagAction {
+            id: entry.get_id(),
+            tag: String::from(tag),
+        }
+    }
+}
+
+impl Action for AddTagAction {
+    fn activate(&self, nm: &mut note_manager::NoteManager) {
+        nm.root.get_mut_child_by_id(self.id).unwrap().add_tag(self.tag.as_str());
+        nm.mark_changed(self.id);
+        nm.refresh_filter();
+        nm.render();
+    }
+
+    fn undo(&self, nm: &mut note_manager::NoteManager) {
+        nm.root.get_mut_child_by_id(self.id).unwrap().remove_tag(self.tag.as_str());
+        nm.mark_changed(self.id);
+        nm.refresh_filter();
+        nm.render();
+    }
+}
+
+impl RemoveTagAction {
+    pub fn new(entry: &notes::Entry, tag: &str) -> RemoveTagAction {
+        RemoveTagAction {
+            id: entry.get_id(),
+            tag: String::from(tag),
+        }
+    }
+}
+
+impl Action for RemoveTagAction {
+    fn activate(&self, nm: &mut note_manager::NoteManager) {
+        nm.root.get_mut_child_by_id(self.id).unwrap().remove_tag(self.tag.as_str());
+        nm.mark_changed(self.id);
+        nm.refresh_filter();
+        nm.render();
+    }
+
+    fn undo(&self, nm: &mut note_manager::NoteManager) {
+        nm.root.get_mut_child_by_id(self.id).unwrap().add_tag(self.tag.as_str());
+        nm.mark_changed(self.id);
+        nm.refresh_filter();
+        nm.render();
+    }
+}
+
+impl GroupAction {
+    pub fn new(actions: Vec<Box<Action>>) -> GroupAction {
+        GroupAction {
+            actions: actions,
+        } 
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl Action for GroupAction {
+    fn activate(&self, nm: &mut note_manager::NoteManager) {
+        for action in self.actions.iter() {
+            action.activate(nm);
+        }
+    }
+
+    fn undo(&self, nm: &mut note_manager::NoteManager) {
+        for action in self.actions.iter().rev() {
+            action.undo(nm);
+        }
+    }
+}
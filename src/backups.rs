@@ -0,0 +1,99 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
+
+//Milliseconds keep saves made within the same second apart, older snapshots only have seconds
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+const OLD_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+pub fn default_backup_dir(file_name: &str) -> String {
+    format!("{}.snapshots", file_name)
+}
+
+pub fn create_snapshot(file_name: &str, backup_dir: &str, backup_count: usize) -> io::Result<Option<String>> {
+    if backup_count == 0 || !Path::new(file_name).exists() {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(backup_dir)?;
+
+    //Never overwrite a snapshot, even if the clock gives the same time twice
+    let mut time = Local::now();
+    let mut snapshot = Path::new(backup_dir).join(format!("{}.{}", snapshot_prefix(file_name), time.format(TIME_FORMAT)));
+    while snapshot.exists() {
+        time = time + Duration::milliseconds(1);
+        snapshot = Path::new(backup_dir).join(format!("{}.{}", snapshot_prefix(file_name), time.format(TIME_FORMAT)));
+    }
+
+    fs::copy(file_name, &snapshot)?;
+
+    //Remove the oldest snapshots so only backup_count of them are kept
+    for old_snapshot in list_snapshots(file_name, backup_dir).iter().skip(backup_count) {
+        fs::remove_file(old_snapshot)?;
+    }
+
+    Ok(Some(snapshot.to_string_lossy().into_owned()))
+}
+
+//Newest first. The backup dir can be the notebook's own directory, so only names ending in a snapshot time count
+pub fn list_snapshots(file_name: &str, backup_dir: &str) -> Vec<String> {
+    let prefix = format!("{}.", snapshot_prefix(file_name));
+    let mut snapshots = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(backup_dir) {
+        for entry in entries {
+            if let Ok(entry) = entry {
+                let name = entry.file_name().to_string_lossy().into_owned();
+
+                if name.starts_with(prefix.as_str()) {
+                    if let Some(time) = parse_snapshot_time(&name[prefix.len()..]) {
+                        snapshots.push((time, entry.path().to_string_lossy().into_owned()));
+                    }
+                }
+            }
+        }
+    }
+
+    snapshots.sort();
+    snapshots.into_iter().rev().map(|(_, snapshot)| snapshot).collect()
+}
+
+pub fn find_snapshot(file_name: &str, backup_dir: &str, snapshot: &str) -> Option<String> {
+    if Path::new(snapshot).is_file() {
+        return Some(String::from(snapshot));
+    }
+
+    for candidate in list_snapshots(file_name, backup_dir) {
+        let candidate_name = Path::new(candidate.as_str()).file_name().unwrap().to_string_lossy().into_owned();
+
+        if candidate_name == snapshot || candidate_name.ends_with(format!(".{}", snapshot).as_str()) {
+            return Some(candidate);
+        }
+    }
+
+    None
+}
+
+pub fn snapshot_label(snapshot: &str) -> String {
+    let name = Path::new(snapshot).file_name().unwrap().to_string_lossy().into_owned();
+
+    match parse_snapshot_time(&name[name.rfind('.').map(|i| i + 1).unwrap_or(0)..]) {
+        Some(time) => format!("{} ({})", time.format("%Y-%m-%d %H:%M:%S"), name),
+        None => name,
+    }
+}
+
+fn parse_snapshot_time(time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT).or(NaiveDateTime::parse_from_str(time, OLD_TIME_FORMAT)).ok()
+}
+
+fn snapshot_prefix(file_name: &str) -> String {
+    match Path::new(file_name).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => String::from(file_name),
+    }
+}
@@ -0,0 +1,776 @@
+extern crate json;
+
+use std::result::Result;
+use import;
+use note_manager;
+use actions::*;
+use store;
+use export;
+use outline;
+use std::fs;
+use std::path::Path;
+use notes;
+use finder;
+use search;
+use config;
+
+const COMMANDS: [&str; 12] = ["import", "export", "reindex", "ls", "show", "add-note", "add-category", "mv", "rm", "rename", "find", "open"];
+//The fields every --format template can use
+const ENTRY_FIELDS: [&str; 7] = ["id", "name", "path", "file", "tags", "created", "is_category"];
+
+pub fn is_command(arg: &str) -> bool {
+    COMMANDS.contains(&arg)
+}
+
+pub fn usage(program: &str) -> String {
+    format!("Usage: {0} import <file> <directory> [--category <id>] [--include <glob>]... [--exclude <glob>]... [--max-depth <n>] [--dry-run]
+       {0} import <file> <outline file> [--format <opml|markdown>] [--category <id>] [--dry-run]
+       {0} export <file> --format <markdown|html|opml> --output <path> [--inline]
+       {0} reindex <file>
+       {0} ls <file> [entry] [--json | --format <template>]
+       {0} show <file> <entry> [--json | --format <template>]
+       {0} add-note <file> <category> <name> [--file <path>]
+       {0} add-category <file> <category> <name> [--dir <path>]
+       {0} mv <file> <entry> <category>
+       {0} rm <file> <entry>
+       {0} rename <file> <entry> <name>
+       {0} find <file> <query> [--content] [--json | --format <template>]
+       {0} open <file> <entry> [--line <n>] [--with <command>]
+Entries are given by id or by a path of names like Work/Meetings, start the path with / if a name is a number
+--json prints entries as objects with id, name, path (list of names), file_location (or null), tags, created (unix time) and is_category
+  ls --json nests the entries under each category in children, show --json lists their ids in children_ids
+--format templates can use {{id}}, {{name}}, {{path}}, {{file}}, {{tags}}, {{created}} (RFC 3339), {{is_category}}, {{indent}} for ls and {{line}}, {{snippet}} for find --content", program)
+}
+
+//args starts at the command name, returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    let result = match args[0].as_str() {
+        "import" => import_command(&args[1..]),
+        "export" => export_command(&args[1..]),
+        "reindex" => reindex_command(&args[1..]),
+        "ls" => ls_command(&args[1..]),
+        "show" => show_command(&args[1..]),
+        "add-note" => add_command(&args[1..], false),
+        "add-category" => add_command(&args[1..], true),
+        "mv" => mv_command(&args[1..]),
+        "rm" => rm_command(&args[1..]),
+        "rename" => rename_command(&args[1..]),
+        "find" => find_command(&args[1..]),
+        "open" => open_command(&args[1..]),
+        _ => Err(format!("Unknown command {}", args[0])),
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        },
+    }
+}
+
+enum Output {
+    TEXT,
+    JSON,
+    FORMAT(String),
+}
+
+struct Arguments {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+    flags: Vec<String>,
+}
+
+impl Arguments {
+    fn parse(args: &[String], options_with_values: &[&str]) -> Result<Arguments, String> {
+        let mut arguments = Arguments {
+            positional: Vec::new(),
+            options: Vec::new(),
+            flags: Vec::new(),
+        };
+
+        let mut i = 0;
+        while i < args.len() {
+            if options_with_values.contains(&args[i].as_str()) {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} needs a value", args[i]));
+                }
+
+                arguments.options.push((args[i].clone(), args[i + 1].clone()));
+                i += 1;
+            } else if args[i].starts_with("--") {
+                arguments.flags.push(args[i].clone());
+            } else {
+                arguments.positional.push(args[i].clone());
+            }
+
+            i += 1;
+        }
+
+        Ok(arguments)
+    }
+
+    fn get(&self, option: &str) -> Option<&str> {
+        self.options.iter().rev().find(|&&(ref name, _)| name == option).map(|&(_, ref value)| value.as_str())
+    }
+
+    fn get_all(&self, option: &str) -> Vec<&str> {
+        self.options.iter().filter(|&&(ref name, _)| name == option).map(|&(_, ref value)| value.as_str()).collect()
+    }
+
+    fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    fn take_flag(&mut self, flag: &str) -> bool {
+        let had_flag = self.has_flag(flag);
+        self.flags.retain(|f| f != flag);
+
+        had_flag
+    }
+
+    //For queries --json is about the output, not the kind of notebook
+    //extra_fields are the ones the command fills in on top of ENTRY_FIELDS
+    fn get_output(&mut self, extra_fields: &[&str]) -> Result<Output, String> {
+        let json = self.take_flag("--json");
+
+        match (json, self.get("--format")) {
+            (true, Some(_)) => Err(String::from("--json and --format can not be used together")),
+            (true, None) => Ok(Output::JSON),
+            (false, Some(template)) => {
+                let template = unescape_template(template);
+                check_template(template.as_str(), extra_fields)?;
+
+                Ok(Output::FORMAT(template))
+            },
+            (false, None) => Ok(Output::TEXT),
+        }
+    }
+
+    fn get_number(&self, option: &str) -> Result<Option<u32>, String> {
+        match self.get(option) {
+            Some(value) => match value.parse::<u32>() {
+                Ok(n) => Ok(Some(n)),
+                Err(_) => Err(format!("{} needs a number", option)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn get_positional(&self, i: usize, name: &str) -> Result<&str, String> {
+        match self.positional.get(i) {
+            Some(value) => Ok(value.as_str()),
+            None => Err(format!("You need to input {}", name)),
+        }
+    }
+
+    fn open_notebook(&self) -> Result<note_manager::NoteManager, String> {
+        let file = match self.positional.first() {
+            Some(file) => file.clone(),
+            None => return Err(String::from("You need to input a file name")),
+        };
+
+        let store_kind = if self.has_flag("--sqlite") {
+            store::StoreKind::SQLITE
+        } else if self.has_flag("--json") {
+            store::StoreKind::JSON
+        } else {
+            store::kind_for_file(file.as_str())
+        };
+
+        let settings = config::Config::load()?.settings_for(file.as_str());
+
+        let mut nm = note_manager::NoteManager::new_headless(file, store_kind);
+        settings.apply(&mut nm);
+
+        Ok(nm)
+    }
+}
+
+//Snapshots are only taken when an interactive session ends, scripts running many commands would rotate them all away
+fn save(nm: &mut note_manager::NoteManager) -> Result<(), String> {
+    let result = nm.save().map_err(|e| format!("Failed to save: {}", e));
+    nm.close();
+
+    result
+}
+
+fn import_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &["--category", "--include", "--exclude", "--max-depth", "--format"])?;
+
+    let directory = match arguments.positional.get(1) {
+        Some(directory) => directory.clone(),
+        None => return Err(String::from("You need to input a directory or outline file to import")),
+    };
+
+    if Path::new(directory.as_str()).is_file() {
+        return import_outline(&arguments, directory.as_str());
+    }
+
+    let mut options = import::ImportOptions::new();
+    for pattern in arguments.get_all("--include") {
+        options.add_include(pattern)?;
+    }
+    for pattern in arguments.get_all("--exclude") {
+        options.add_exclude(pattern)?;
+    }
+    options.max_depth = arguments.get_number("--max-depth")?.map(|depth| depth as usize);
+
+    let mut nm = arguments.open_notebook()?;
+
+    let category_id = match arguments.get_number("--category")? {
+        Some(id) => id,
+        None => nm.root.get_id(),
+    };
+
+    match nm.root.get_child_by_id(category_id) {
+        Some(category) if category.can_add_child() => (),
+        _ => return Err(format!("No category with id {}", category_id)),
+    }
+
+    let mut next_id = nm.next_id;
+    let entry = match import::import_directory(directory.as_str(), &options, &mut next_id) {
+        Ok(entry) => entry,
+        Err(e) => return Err(format!("Could not import {}: {}", directory, e)),
+    };
+
+    for line in import::preview(&entry) {
+        println!("{}", line);
+    }
+
+    if arguments.has_flag("--dry-run") {
+        println!("Dry run, {} entries would be imported", import::count_entries(&entry));
+        return Ok(());
+    }
+
+    let count = import::count_entries(&entry);
+    let add = AddAction::new(category_id, entry);
+    add.activate(&mut nm);
+    nm.actions.add(Box::new(add));
+
+    save(&mut nm)?;
+    println!("Imported {} entries", count);
+
+    Ok(())
+}
+
+fn import_outline(arguments: &Arguments, file: &str) -> Result<(), String> {
+    let format = match arguments.get("--format") {
+        Some(format) => outline::get_format_from_str(format)?,
+        None => outline::format_for_file(file),
+    };
+
+    let contents = fs::read_to_string(file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+    let items = outline::parse(contents.as_str(), format).map_err(|e| format!("Could not parse {}: {}", file, e))?;
+
+    for line in outline::preview(&items) {
+        println!("{}", line);
+    }
+
+    let count = outline::count_items(&items);
+    if arguments.has_flag("--dry-run") {
+        println!("Dry run, {} entries would be imported", count);
+        return Ok(());
+    }
+
+    let mut nm = arguments.open_notebook()?;
+
+    let category_id = match arguments.get_number("--category")? {
+        Some(id) => id,
+        None => nm.root.get_id(),
+    };
+
+    let location = match nm.root.get_child_by_id(category_id) {
+        Some(category) if category.can_add_child() => outline::category_location(category),
+        _ => return Err(format!("No category with id {}", category_id)),
+    };
+
+    let mut next_id = nm.next_id;
+    let mut add_actions: Vec<Box<Action>> = Vec::new();
+
+    for entry in outline::build_entries(&items, location.as_str(), &mut next_id) {
+        add_actions.push(Box::new(AddAction::new(category_id, entry)));
+    }
+
+    let import = GroupAction::new(add_actions);
+    import.activate(&mut nm);
+    nm.actions.add(Box::new(import));
+
+    save(&mut nm)?;
+    println!("Imported {} entries", count);
+
+    Ok(())
+}
+
+fn export_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &["--format", "--output"])?;
+
+    let format = match arguments.get("--format") {
+        Some(format) => export::get_format_from_str(format)?,
+        None => return Err(String::from("You need to choose a format with --format")),
+    };
+
+    let output = match arguments.get("--output") {
+        Some(output) => output,
+        None => return Err(String::from("You need to choose where to export to with --output")),
+    };
+
+    let mut nm = arguments.open_notebook()?;
+    let result = export::export(&nm.root, nm.get_title(), format, output, arguments.has_flag("--inline")).map_err(|e| format!("Could not export to {}: {}", output, e));
+    nm.close();
+
+    result
+}
+
+fn reindex_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &[])?;
+
+    let mut nm = arguments.open_notebook()?;
+    let result = nm.rebuild_index().map_err(|e| format!("Could not save the search index: {}", e));
+    nm.close();
+
+    println!("Indexed {} notes", result?);
+
+    Ok(())
+}
+
+//An entry is either its id or the names on the way to it separated by slashes
+fn find_entry(root: &notes::Entry, spec: &str) -> Result<u32, String> {
+    let spec = spec.trim();
+
+    if !spec.starts_with("/") {
+        if let Ok(id) = spec.parse::<u32>() {
+            return match root.get_child_by_id(id) {
+                Some(entry) => Ok(entry.get_id()),
+                None => Err(format!("No entry with id {}", id)),
+            };
+        }
+    }
+
+    let mut entry = root;
+    let mut path = String::new();
+
+    for name in spec.split('/').filter(|name| !name.is_empty()) {
+        path.push_str(format!("/{}", name).as_str());
+
+        let matches: Vec<&notes::Entry> = entry.get_children().into_iter().filter(|child| child.text == name).collect();
+        entry = match matches.len() {
+            0 => return Err(format!("No entry named {}", path)),
+            1 => matches[0],
+            _ => {
+                let ids: Vec<String> = matches.iter().map(|child| child.get_id().to_string()).collect();
+                return Err(format!("{} is ambiguous, use one of the ids {}", path, ids.join(", ")));
+            },
+        };
+    }
+
+    Ok(entry.get_id())
+}
+
+fn find_category(root: &notes::Entry, spec: &str) -> Result<u32, String> {
+    let id = find_entry(root, spec)?;
+
+    match root.get_child_by_id(id) {
+        Some(entry) if entry.can_add_child() => Ok(id),
+        _ => Err(format!("{} is not a category", spec)),
+    }
+}
+
+fn ls_command(args: &[String]) -> Result<(), String> {
+    let mut arguments = Arguments::parse(args, &["--format"])?;
+    let output = arguments.get_output(&["indent"])?;
+
+    let mut nm = arguments.open_notebook()?;
+    let result = match arguments.positional.get(1) {
+        Some(spec) => find_entry(&nm.root, spec.as_str()),
+        None => Ok(nm.root.get_id()),
+    };
+
+    if let Ok(id) = result {
+        let entry = nm.root.get_child_by_id(id).unwrap();
+        let entries = if id == nm.root.get_id() { entry.get_children() } else { vec![entry] };
+
+        match output {
+            Output::JSON => {
+                let json_entries: Vec<json::JsonValue> = entries.iter().map(|entry| get_tree_json(&nm.root, entry)).collect();
+                println!("{}", json::stringify_pretty(json_entries, 2));
+            },
+            _ => for entry in entries {
+                print_tree(&nm.root, entry, 0, &output);
+            },
+        }
+    }
+    nm.close();
+
+    result.map(|_| ())
+}
+
+fn show_command(args: &[String]) -> Result<(), String> {
+    let mut arguments = Arguments::parse(args, &["--format"])?;
+    let output = arguments.get_output(&[])?;
+
+    let entry_spec = arguments.get_positional(1, "an entry to show")?;
+
+    let mut nm = arguments.open_notebook()?;
+    let result = find_entry(&nm.root, entry_spec);
+
+    if let Ok(id) = result {
+        let entry = nm.root.get_child_by_id(id).unwrap();
+
+        match output {
+            Output::TEXT => {
+                for (name, value) in get_entry_fields(&nm.root, entry) {
+                    println!("{}: {}", name, value);
+                }
+
+                let children: Vec<String> = entry.get_children().iter().map(|child| child.get_id().to_string()).collect();
+                println!("children: {}", children.join(", "));
+            },
+            Output::JSON => {
+                let mut json_entry = get_entry_json(&nm.root, entry);
+                json_entry["children_ids"] = entry.get_children().iter().map(|child| child.get_id()).collect::<Vec<u32>>().into();
+
+                println!("{}", json::stringify_pretty(json_entry, 2));
+            },
+            Output::FORMAT(ref template) => println!("{}", fill_template(template, &get_entry_fields(&nm.root, entry))),
+        }
+    }
+    nm.close();
+
+    result.map(|_| ())
+}
+
+//The JSON every query prints for an entry. Fields are only ever added, never renamed or removed:
+//id, name, path (list of names from the top), file_location (string or null), tags (list), created (unix time) and is_category
+fn get_entry_json(root: &notes::Entry, entry: &notes::Entry) -> json::JsonValue {
+    let mut fields = entry.get_fields_as_json();
+
+    let mut json_entry = json::JsonValue::new_object();
+    json_entry["id"] = fields.remove("id");
+    json_entry["name"] = fields.remove("text");
+    json_entry["path"] = get_entry_path(root, entry).into();
+    json_entry["file_location"] = fields.remove("file_location");
+    json_entry["tags"] = fields.remove("tags");
+    json_entry["created"] = fields.remove("time_created");
+    json_entry["is_category"] = fields.remove("is_category");
+
+    json_entry
+}
+
+fn get_tree_json(root: &notes::Entry, entry: &notes::Entry) -> json::JsonValue {
+    let mut json_entry = get_entry_json(root, entry);
+    json_entry["children"] = entry.get_children().iter().map(|child| get_tree_json(root, child)).collect::<Vec<json::JsonValue>>().into();
+
+    json_entry
+}
+
+fn get_entry_path(root: &notes::Entry, entry: &notes::Entry) -> Vec<String> {
+    let mut path = vec![entry.text.clone()];
+
+    let mut id = entry.get_id();
+    while let Some(parent) = root.get_parent_by_id(id) {
+        if parent.get_id() == root.get_id() {
+            break;
+        }
+
+        path.insert(0, parent.text.clone());
+        id = parent.get_id();
+    }
+
+    path
+}
+
+fn get_entry_fields(root: &notes::Entry, entry: &notes::Entry) -> Vec<(&'static str, String)> {
+    let tags: Vec<&str> = entry.get_tags().iter().map(|tag| tag.as_str()).collect();
+
+    let values = vec![
+        entry.get_id().to_string(),
+        entry.text.clone(),
+        get_entry_path(root, entry).join("/"),
+        entry.file_location.clone(),
+        tags.join(","),
+        entry.time_created.to_rfc3339(),
+        entry.can_add_child().to_string(),
+    ];
+
+    ENTRY_FIELDS.iter().cloned().zip(values).collect()
+}
+
+//Values are put in as they are, a name containing {tags} does not get filled in a second time
+fn fill_template(template: &str, fields: &Vec<(&str, String)>) -> String {
+    let filled = scan_template(template, |name| {
+        Ok(fields.iter().find(|&&(field, _)| field == name).map(|&(_, ref value)| value.clone()).unwrap_or(String::new()))
+    });
+
+    filled.unwrap_or(String::new())
+}
+
+fn check_template(template: &str, extra_fields: &[&str]) -> Result<(), String> {
+    scan_template(template, |name| {
+        if ENTRY_FIELDS.contains(&name) || extra_fields.contains(&name) {
+            Ok(String::new())
+        } else {
+            let known: Vec<&str> = ENTRY_FIELDS.iter().chain(extra_fields.iter()).cloned().collect();
+            Err(format!("Unknown field {{{}}} in --format, use one of {{{}}}", name, known.join("}, {")))
+        }
+    }).map(|_| ())
+}
+
+//Goes over the template once, {name} is replaced with what field returns for it and anything else is kept
+fn scan_template<F: FnMut(&str) -> Result<String, String>>(template: &str, mut field: F) -> Result<String, String> {
+    let mut line = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        line.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let name = rest[1..].find('}').map(|end| &rest[1..end + 1]).filter(|name| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'));
+
+        match name {
+            Some(name) => {
+                line.push_str(field(name)?.as_str());
+                rest = &rest[name.len() + 2..];
+            },
+            None => {
+                line.push('{');
+                rest = &rest[1..];
+            },
+        }
+    }
+    line.push_str(rest);
+
+    Ok(line)
+}
+
+//Lets \t and \n be used in templates given on the command line
+fn unescape_template(template: &str) -> String {
+    template.replace("\\t", "\t").replace("\\n", "\n")
+}
+
+fn print_tree(root: &notes::Entry, entry: &notes::Entry, depth: usize, output: &Output) {
+    match *output {
+        Output::FORMAT(ref template) => {
+            let mut fields = get_entry_fields(root, entry);
+            fields.push(("indent", "  ".repeat(depth)));
+
+            println!("{}", fill_template(template, &fields));
+        },
+        _ => print_entry(entry, depth),
+    }
+
+    for child in entry.get_children() {
+        print_tree(root, child, depth + 1, output);
+    }
+}
+
+fn print_entry(entry: &notes::Entry, depth: usize) {
+    let mut line = format!("{}{}", "  ".repeat(depth), entry.text);
+    if entry.can_add_child() {
+        line.push('/');
+    }
+
+    line.push_str(format!(" [{}]", entry.get_id()).as_str());
+
+    for tag in entry.get_tags().iter() {
+        line.push_str(format!(" #{}", tag).as_str());
+    }
+
+    if entry.file_location.as_str() != "" {
+        line.push_str(format!(" -> {}", entry.file_location).as_str());
+    }
+
+    println!("{}", line);
+}
+
+fn add_command(args: &[String], is_category: bool) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &["--file", "--dir"])?;
+
+    let category_spec = arguments.get_positional(1, "a category to add to")?;
+    let name = arguments.get_positional(2, "a name")?;
+    let location = if is_category { arguments.get("--dir") } else { arguments.get("--file") };
+
+    if name.trim().is_empty() {
+        return Err(String::from("The name can not be empty"));
+    }
+    if location.map_or(false, |location| location.trim().is_empty()) {
+        return Err(format!("The {} can not be empty", if is_category { "directory" } else { "file" }));
+    }
+
+    let mut nm = arguments.open_notebook()?;
+    let category_id = find_category(&nm.root, category_spec)?;
+
+    //Without a location the file or directory is named after the entry, inside the category's directory
+    let location = String::from(location.unwrap_or(name));
+    let id = nm.next_id;
+
+    let entry = notes::EntryBuilder::new(id).set_text(name).set_is_category(is_category).set_file_location(location, &nm, category_id).build();
+    let add = AddAction::new(category_id, entry);
+    add.activate(&mut nm);
+    nm.actions.add(Box::new(add));
+
+    save(&mut nm)?;
+    println!("{}", id);
+
+    Ok(())
+}
+
+fn mv_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &[])?;
+
+    let entry_spec = arguments.get_positional(1, "an entry to move")?;
+    let category_spec = arguments.get_positional(2, "a category to move it to")?;
+
+    let mut nm = arguments.open_notebook()?;
+    let id = find_entry(&nm.root, entry_spec)?;
+    let category_id = find_category(&nm.root, category_spec)?;
+
+    if id == nm.root.get_id() {
+        return Err(String::from("The root can not be moved"));
+    }
+    if nm.root.get_child_by_id(id).unwrap().get_child_by_id(category_id).is_some() {
+        return Err(String::from("A category can not be moved into itself"));
+    }
+
+    let old_category_id = nm.root.get_parent_by_id(id).unwrap().get_id();
+    let move_action = MoveAction::new(id, old_category_id, category_id);
+    move_action.activate(&mut nm);
+    nm.actions.add(Box::new(move_action));
+
+    save(&mut nm)
+}
+
+fn rm_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &[])?;
+
+    let entry_spec = arguments.get_positional(1, "an entry to remove")?;
+
+    let mut nm = arguments.open_notebook()?;
+    let id = find_entry(&nm.root, entry_spec)?;
+
+    if id == nm.root.get_id() {
+        return Err(String::from("The root can not be removed"));
+    }
+
+    let delete = DeleteAction::new(id);
+    delete.activate(&mut nm);
+    nm.actions.add(Box::new(delete));
+
+    save(&mut nm)
+}
+
+fn rename_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &[])?;
+
+    let entry_spec = arguments.get_positional(1, "an entry to rename")?;
+    let name = arguments.get_positional(2, "a new name")?;
+
+    if name.trim().is_empty() {
+        return Err(String::from("The name can not be empty"));
+    }
+
+    let mut nm = arguments.open_notebook()?;
+    let id = find_entry(&nm.root, entry_spec)?;
+
+    let rename = RenameAction::new(nm.root.get_child_by_id(id).unwrap(), name);
+    rename.activate(&mut nm);
+    nm.actions.add(Box::new(rename));
+
+    save(&mut nm)
+}
+
+fn find_command(args: &[String]) -> Result<(), String> {
+    let mut arguments = Arguments::parse(args, &["--format"])?;
+    let output = match arguments.has_flag("--content") {
+        true => arguments.get_output(&["line", "snippet"])?,
+        false => arguments.get_output(&[])?,
+    };
+
+    let query = arguments.get_positional(1, "something to find")?;
+
+    let mut nm = arguments.open_notebook()?;
+    let mut json_results = Vec::new();
+    let mut found = 0;
+
+    if arguments.has_flag("--content") {
+        let query = match search::Query::parse(query) {
+            Ok(query) => query,
+            Err(e) => {
+                nm.close();
+                return Err(format!("Invalid search: {}", e));
+            },
+        };
+
+        for result in nm.search_contents(&query) {
+            let entry = nm.root.get_child_by_id(result.id).unwrap();
+            found += 1;
+
+            match output {
+                Output::TEXT => println!("{}\t{}:{}: {}", result.id, result.file, result.line, result.snippet),
+                Output::JSON => {
+                    let mut json_result = get_entry_json(&nm.root, entry);
+                    json_result["line"] = result.line.into();
+                    json_result["snippet"] = result.snippet.as_str().into();
+
+                    json_results.push(json_result);
+                },
+                Output::FORMAT(ref template) => {
+                    let mut fields = get_entry_fields(&nm.root, entry);
+                    fields.push(("line", result.line.to_string()));
+                    fields.push(("snippet", result.snippet.clone()));
+
+                    println!("{}", fill_template(template, &fields));
+                },
+            }
+        }
+    } else {
+        let candidates = finder::collect_candidates(&nm.root);
+
+        for i in finder::search(query, &candidates) {
+            let entry = nm.root.get_child_by_id(candidates[i].id).unwrap();
+            found += 1;
+
+            match output {
+                Output::TEXT => println!("{}\t{}", candidates[i].id, candidates[i].text),
+                Output::JSON => json_results.push(get_entry_json(&nm.root, entry)),
+                Output::FORMAT(ref template) => println!("{}", fill_template(template, &get_entry_fields(&nm.root, entry))),
+            }
+        }
+    }
+    nm.close();
+
+    //An empty list is still valid JSON, so scripts get it along with the exit code
+    if let Output::JSON = output {
+        println!("{}", json::stringify_pretty(json_results, 2));
+    }
+
+    if found == 0 {
+        return Err(String::from("No matches found"));
+    }
+
+    Ok(())
+}
+
+fn open_command(args: &[String]) -> Result<(), String> {
+    let arguments = Arguments::parse(args, &["--line", "--with"])?;
+
+    let entry_spec = arguments.get_positional(1, "an entry to open")?;
+    let line = arguments.get_number("--line")?.map(|line| line as usize);
+
+    let mut nm = arguments.open_notebook()?;
+    let result = find_entry(&nm.root, entry_spec).and_then(|id| {
+        let entry = nm.root.get_child_by_id(id).unwrap();
+
+        if !entry.has_file() {
+            return Err(format!("{} is not a note with a file", entry_spec));
+        }
+
+        match arguments.get("--with") {
+            Some(command) => entry.open_file_with(command, line),
+            None => entry.open_file(nm.get_opener(), line),
+        }
+    });
+    nm.close();
+
+    result
+}
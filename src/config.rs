@@ -0,0 +1,537 @@
+extern crate toml;
+
+use pancurses::*;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::result::Result;
+use std::time::Duration;
+use note_manager;
+use opener;
+use keybind_manager;
+use keys;
+
+const SETTINGS: [&str; 11] = ["title", "editor", "indent", "autosave", "autosave_actions", "backups", "backup_dir", "colors", "openers", "keys", "key_timeout"];
+const COLORS: [&str; 4] = ["foreground", "background", "highlight_foreground", "highlight_background"];
+
+#[derive(Clone, Default)]
+pub struct Colors {
+    pub foreground: Option<i16>,
+    pub background: Option<i16>,
+    pub highlight_foreground: Option<i16>,
+    pub highlight_background: Option<i16>,
+}
+
+//Everything that can be set globally and overridden for a single notebook
+#[derive(Clone, Default)]
+pub struct Settings {
+    pub title: Option<String>,
+    pub editor: Option<String>,
+    pub indent: Option<i32>,
+    pub autosave: Option<u64>,
+    pub autosave_actions: Option<u64>,
+    pub backups: Option<usize>,
+    pub backup_dir: Option<String>,
+    pub colors: Colors,
+    pub openers: Vec<(String, String)>,
+    pub keys: Vec<(keybind_manager::KeybindMode, Vec<keys::Key>, String)>,
+    pub key_timeout: Option<u64>,
+}
+
+pub struct Config {
+    pub notebook: Option<String>,
+    settings: Settings,
+    notebooks: Vec<(PathBuf, Settings)>,
+}
+
+//$XDG_CONFIG_HOME/note_manager/config.toml
+pub fn config_file() -> Option<PathBuf> {
+    xdg_directory("XDG_CONFIG_HOME", ".config").map(|directory| directory.join("note_manager").join("config.toml"))
+}
+
+//$XDG_DATA_HOME/note_manager/notes.json, used when no notebook is given
+pub fn default_notebook() -> Option<PathBuf> {
+    xdg_directory("XDG_DATA_HOME", ".local/share").map(|directory| directory.join("note_manager").join("notes.json"))
+}
+
+fn xdg_directory(variable: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(variable).filter(|directory| !directory.is_empty()) {
+        Some(directory) => Some(PathBuf::from(directory)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)),
+    }
+}
+
+pub fn expand_home(path: &str) -> String {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = env::var_os("HOME") {
+            return format!("{}{}", home.to_string_lossy(), &path[1..]);
+        }
+    }
+
+    String::from(path)
+}
+
+//Notebooks are matched on their absolute path so ~/notes.json and a relative path both find the same overrides
+fn absolute_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(expand_home(path));
+
+    let path = match path.is_absolute() {
+        true => path,
+        false => env::current_dir().map(|directory| directory.join(&path)).unwrap_or(path),
+    };
+
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            notebook: None,
+            settings: Settings::default(),
+            notebooks: Vec::new(),
+        }
+    }
+
+    //A missing config file is fine, a broken one is an error so it does not get silently ignored
+    pub fn load() -> Result<Config, String> {
+        let file = match config_file() {
+            Some(file) => file,
+            None => return Ok(Config::new()),
+        };
+
+        match fs::read_to_string(&file) {
+            Ok(source) => Config::parse(source.as_str(), file.to_string_lossy().as_ref()),
+            Err(_) => Ok(Config::new()),
+        }
+    }
+
+    pub fn parse(source: &str, file_name: &str) -> Result<Config, String> {
+        let value = match source.parse::<toml::Value>() {
+            Ok(value) => value,
+            Err(e) => return Err(format!("{}: {}", file_name, e)),
+        };
+
+        let checker = Checker {
+            source: source,
+            file_name: file_name,
+        };
+
+        let table = value.as_table().unwrap();
+        let mut config = Config::new();
+
+        for (key, value) in table.iter() {
+            match key.as_str() {
+                "notebook" => config.notebook = Some(expand_home(checker.get_str(value, "", key)?)),
+                "notebooks" => {
+                    let notebooks = checker.get_table(value, "", key)?;
+
+                    for (path, notebook_value) in notebooks.iter() {
+                        let table_name = format!("notebooks.\"{}\"", path);
+                        let notebook_table = checker.get_table(notebook_value, "notebooks", path)?;
+
+                        config.notebooks.push((absolute_path(path), checker.get_settings(notebook_table, table_name.as_str())?));
+                    }
+                },
+                _ if SETTINGS.contains(&key.as_str()) => (),
+                _ => return Err(checker.error("", key, format!("unknown setting `{}`", key))),
+            }
+        }
+
+        config.settings = checker.get_settings(table, "")?;
+
+        Ok(config)
+    }
+
+    pub fn get_notebook(&self) -> Option<String> {
+        match self.notebook {
+            Some(ref notebook) => Some(notebook.clone()),
+            None => default_notebook().map(|notebook| notebook.to_string_lossy().into_owned()),
+        }
+    }
+
+    //The global settings with the ones set for this notebook on top
+    pub fn settings_for(&self, notebook: &str) -> Settings {
+        let mut settings = self.settings.clone();
+        let notebook = absolute_path(notebook);
+
+        for &(ref path, ref overrides) in self.notebooks.iter() {
+            if *path == notebook {
+                settings.merge(overrides);
+            }
+        }
+
+        settings
+    }
+}
+
+impl Settings {
+    fn merge(&mut self, other: &Settings) {
+        fn take<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                *value = other.clone();
+            }
+        }
+
+        take(&mut self.title, &other.title);
+        take(&mut self.editor, &other.editor);
+        take(&mut self.indent, &other.indent);
+        take(&mut self.autosave, &other.autosave);
+        take(&mut self.autosave_actions, &other.autosave_actions);
+        take(&mut self.backups, &other.backups);
+        take(&mut self.backup_dir, &other.backup_dir);
+        take(&mut self.key_timeout, &other.key_timeout);
+        take(&mut self.colors.foreground, &other.colors.foreground);
+        take(&mut self.colors.background, &other.colors.background);
+        take(&mut self.colors.highlight_foreground, &other.colors.highlight_foreground);
+        take(&mut self.colors.highlight_background, &other.colors.highlight_background);
+
+        //Notebook rules are checked first, the global ones still apply to anything they don't match
+        let mut openers = other.openers.clone();
+        openers.extend(self.openers.iter().cloned());
+        self.openers = openers;
+
+        //Notebook keys are bound after the global ones so they replace them
+        self.keys.extend(other.keys.iter().cloned());
+    }
+
+    pub fn get_opener(&self) -> opener::Opener {
+        let mut opener = opener::Opener::new();
+        opener.set_editor(self.editor.clone());
+
+        for &(ref pattern, ref command) in self.openers.iter() {
+            //Rules were already checked when the config was parsed
+            let _ = opener.add_rule(pattern.as_str(), command.as_str());
+        }
+
+        opener
+    }
+
+    //Command line options are applied afterwards so they win over the config
+    pub fn apply(&self, nm: &mut note_manager::NoteManager) {
+        if let Some(ref title) = self.title {
+            nm.set_title(title.as_str());
+        }
+
+        if let Some(ref backup_dir) = self.backup_dir {
+            nm.set_backup_dir(backup_dir.as_str());
+        }
+
+        if let Some(backups) = self.backups {
+            nm.set_backup_count(backups);
+        }
+
+        match self.autosave {
+            Some(0) => nm.set_autosave_interval(None),
+            Some(seconds) => nm.set_autosave_interval(Some(Duration::from_secs(seconds))),
+            None => (),
+        }
+
+        if let Some(autosave_actions) = self.autosave_actions {
+            nm.set_autosave_actions(autosave_actions);
+        }
+
+        if let Some(indent) = self.indent {
+            nm.set_indent(indent);
+        }
+
+        nm.set_colors(&self.colors);
+        nm.set_opener(self.get_opener());
+    }
+}
+
+//Turns values into settings, errors point at the line the bad setting is on
+struct Checker<'a> {
+    source: &'a str,
+    file_name: &'a str,
+}
+
+impl<'a> Checker<'a> {
+    fn get_settings(&self, table: &toml::value::Table, table_name: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+
+        for (key, value) in table.iter() {
+            match key.as_str() {
+                "title" => settings.title = Some(String::from(self.get_str(value, table_name, key)?)),
+                "editor" => settings.editor = Some(String::from(self.get_str(value, table_name, key)?)),
+                "indent" => settings.indent = Some(self.get_number(value, table_name, key, 16)? as i32),
+                "autosave" => settings.autosave = Some(self.get_number(value, table_name, key, u32::max_value() as i64)? as u64),
+                "autosave_actions" => settings.autosave_actions = Some(self.get_number(value, table_name, key, u32::max_value() as i64)? as u64),
+                "backups" => settings.backups = Some(self.get_number(value, table_name, key, u32::max_value() as i64)? as usize),
+                "backup_dir" => settings.backup_dir = Some(expand_home(self.get_str(value, table_name, key)?)),
+                "colors" => settings.colors = self.get_colors(value, table_name)?,
+                "openers" => settings.openers = self.get_openers(value, table_name)?,
+                "keys" => settings.keys = self.get_keys(value, table_name)?,
+                "key_timeout" => settings.key_timeout = Some(self.get_number(value, table_name, key, 10000)? as u64),
+                "notebook" | "notebooks" if table_name == "" => (),
+                _ => return Err(self.error(table_name, key, format!("unknown setting `{}`", key))),
+            }
+        }
+
+        Ok(settings)
+    }
+
+    fn get_colors(&self, value: &toml::Value, table_name: &str) -> Result<Colors, String> {
+        let colors_name = sub_table(table_name, "colors");
+        let table = self.get_table(value, table_name, "colors")?;
+        let mut colors = Colors::default();
+
+        for (key, value) in table.iter() {
+            if !COLORS.contains(&key.as_str()) {
+                return Err(self.error(colors_name.as_str(), key, format!("unknown color `{}`, use one of {}", key, COLORS.join(", "))));
+            }
+
+            let name = self.get_str(value, colors_name.as_str(), key)?;
+            let color = match get_color_from_str(name) {
+                Some(color) => color,
+                None => return Err(self.error(colors_name.as_str(), key, format!("unknown color {}, use black, red, green, yellow, blue, magenta, cyan or white", name))),
+            };
+
+            match key.as_str() {
+                "foreground" => colors.foreground = Some(color),
+                "background" => colors.background = Some(color),
+                "highlight_foreground" => colors.highlight_foreground = Some(color),
+                _ => colors.highlight_background = Some(color),
+            }
+        }
+
+        Ok(colors)
+    }
+
+    //[[openers]] keeps the rules in order, the first match wins
+    fn get_openers(&self, value: &toml::Value, table_name: &str) -> Result<Vec<(String, String)>, String> {
+        let openers_name = sub_table(table_name, "openers");
+        let rules = match value.as_array() {
+            Some(rules) => rules,
+            None => return Err(self.error(table_name, "openers", String::from("`openers` must be a list of [[openers]] with a pattern and a command"))),
+        };
+
+        let mut openers = Vec::new();
+        for rule in rules.iter() {
+            let table = match rule.as_table() {
+                Some(table) => table,
+                None => return Err(self.error(table_name, "openers", String::from("every opener needs a pattern and a command"))),
+            };
+
+            let pattern = match table.get("pattern") {
+                Some(pattern) => self.get_str(pattern, openers_name.as_str(), "pattern")?,
+                None => return Err(self.error(table_name, "openers", String::from("an opener is missing its pattern"))),
+            };
+            let command = match table.get("command") {
+                Some(command) => self.get_str(command, openers_name.as_str(), "command")?,
+                None => return Err(self.error(table_name, "openers", format!("the opener for {} is missing its command", pattern))),
+            };
+
+            if let Err(e) = opener::Opener::new().add_rule(pattern, command) {
+                return Err(self.error(openers_name.as_str(), "pattern", e));
+            }
+
+            openers.push((String::from(pattern), String::from(command)));
+        }
+
+        Ok(openers)
+    }
+
+    //[keys.default], [keys.move] and [keys.all] map key sequences to command names
+    fn get_keys(&self, value: &toml::Value, table_name: &str) -> Result<Vec<(keybind_manager::KeybindMode, Vec<keys::Key>, String)>, String> {
+        let keys_name = sub_table(table_name, "keys");
+        let table = self.get_table(value, table_name, "keys")?;
+        let mut keys = Vec::new();
+
+        for (mode_name, mode_value) in table.iter() {
+            let mode = match keybind_manager::get_mode_from_str(mode_name) {
+                Some(mode) => mode,
+                None => return Err(self.error(keys_name.as_str(), mode_name, format!("unknown key mode `{}`, use default, move or all", mode_name))),
+            };
+
+            let mode_table_name = sub_table(keys_name.as_str(), mode_name);
+            let mode_table = self.get_table(mode_value, keys_name.as_str(), mode_name)?;
+
+            for (notation, command) in mode_table.iter() {
+                let command = self.get_str(command, mode_table_name.as_str(), notation)?;
+                let parsed = match keys::parse_keys(notation) {
+                    Ok(parsed) => parsed,
+                    Err(e) => return Err(self.error(mode_table_name.as_str(), notation, e)),
+                };
+
+                keys.push((mode.clone(), parsed, String::from(command)));
+            }
+        }
+
+        Ok(keys)
+    }
+
+    fn get_str<'b>(&self, value: &'b toml::Value, table_name: &str, key: &str) -> Result<&'b str, String> {
+        match value.as_str() {
+            Some(value) => Ok(value),
+            None => Err(self.error(table_name, key, format!("`{}` must be a string", key))),
+        }
+    }
+
+    fn get_number(&self, value: &toml::Value, table_name: &str, key: &str, max: i64) -> Result<i64, String> {
+        match value.as_integer() {
+            Some(value) if value >= 0 && value <= max => Ok(value),
+            _ => Err(self.error(table_name, key, format!("`{}` must be a whole number from 0 to {}", key, max))),
+        }
+    }
+
+    fn get_table<'b>(&self, value: &'b toml::Value, table_name: &str, key: &str) -> Result<&'b toml::value::Table, String> {
+        match value.as_table() {
+            Some(table) => Ok(table),
+            None => Err(self.error(table_name, key, format!("`{}` must be a table", key))),
+        }
+    }
+
+    fn error(&self, table_name: &str, key: &str, message: String) -> String {
+        match find_line(self.source, table_name, key) {
+            Some(line) => format!("{} line {}: {}", self.file_name, line, message),
+            None => format!("{}: {}", self.file_name, message),
+        }
+    }
+}
+
+fn sub_table(table_name: &str, key: &str) -> String {
+    match table_name {
+        "" => String::from(key),
+        _ => format!("{}.{}", table_name, key),
+    }
+}
+
+//toml values don't remember where they came from, so look for the key under its table header in the source
+//table_name is written like a toml header, key is the name as toml gave it to us, so a dot in it is part of the name
+fn find_line(source: &str, table_name: &str, key: &str) -> Option<usize> {
+    let table = split_key(table_name);
+    let mut target = table.clone();
+    target.push(String::from(key));
+
+    let mut current_table = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if line.starts_with("[") {
+            let header_start = if line.starts_with("[[") { 2 } else { 1 };
+            let header = &line[header_start..];
+            let header = split_key(&header[..find_unquoted(header, ']').unwrap_or(header.len())]);
+
+            //An array of tables is reported at its header, a normal table at the key inside it
+            if header == target || (header == table && header_start == 2) {
+                return Some(i + 1);
+            }
+
+            current_table = header;
+        } else if let Some(equals) = find_unquoted(line, '=') {
+            //Dotted keys like colors.foreground = "red" can also set what is being looked for
+            let mut path = current_table.clone();
+            path.extend(split_key(&line[..equals]));
+
+            if path.starts_with(&target) {
+                return Some(i + 1);
+            }
+        }
+    }
+
+    None
+}
+
+//Splits a dotted key into its parts, dots inside quotes are part of the name and spaces around the dots don't count
+fn split_key(key: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quote = None;
+
+    for c in key.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => part.push(c),
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '.' => {
+                    parts.push(part.trim().to_string());
+                    part.clear();
+                },
+                _ => part.push(c),
+            },
+        }
+    }
+
+    if !part.trim().is_empty() || !parts.is_empty() {
+        parts.push(part.trim().to_string());
+    }
+
+    parts
+}
+
+fn find_unquoted(text: &str, target: char) -> Option<usize> {
+    let mut quote = None;
+
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == target => return Some(i),
+            None => (),
+        }
+    }
+
+    None
+}
+
+fn get_color_from_str(color: &str) -> Option<i16> {
+    match color.to_lowercase().as_str() {
+        "black" => Some(COLOR_BLACK),
+        "red" => Some(COLOR_RED),
+        "green" => Some(COLOR_GREEN),
+        "yellow" => Some(COLOR_YELLOW),
+        "blue" => Some(COLOR_BLUE),
+        "magenta" => Some(COLOR_MAGENTA),
+        "cyan" => Some(COLOR_CYAN),
+        "white" => Some(COLOR_WHITE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_keys() {
+        let source = "[keys.default]\n\"<C-d>\" = \"page-down\"\n\"=\" = \"zoom\"\n'.' = \"repeat\"\n\"a.b\" = \"c\"\n";
+
+        assert_eq!(find_line(source, "keys.default", "<C-d>"), Some(2));
+        assert_eq!(find_line(source, "keys.default", "="), Some(3));
+        assert_eq!(find_line(source, "keys.default", "."), Some(4));
+        assert_eq!(find_line(source, "keys.default", "a.b"), Some(5));
+        assert_eq!(find_line(source, "keys.default", "a"), None);
+    }
+
+    #[test]
+    fn quoted_table_names() {
+        let source = "indent = 2\n\n[notebooks.\"/home/me/notes.v2.json\"]\nindent = 3\n\n[ notebooks . '/tmp/[x].json' . keys . all ]\nj = \"cursor-down\"\n";
+
+        assert_eq!(find_line(source, "", "indent"), Some(1));
+        assert_eq!(find_line(source, "notebooks.\"/home/me/notes.v2.json\"", "indent"), Some(4));
+        assert_eq!(find_line(source, "notebooks.\"/tmp/[x].json\".keys.all", "j"), Some(7));
+        assert_eq!(find_line(source, "notebooks.\"/tmp/[x].json\".keys", "all"), Some(6));
+    }
+
+    #[test]
+    fn dotted_keys_and_arrays_of_tables() {
+        let source = "colors.foreground = \"red\"\n\n[[openers]]\npattern = \"*.pdf\"\n";
+
+        assert_eq!(find_line(source, "colors", "foreground"), Some(1));
+        assert_eq!(find_line(source, "", "colors"), Some(1));
+        assert_eq!(find_line(source, "", "openers"), Some(3));
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let source = "title = \"Notes\"\n[notebooks.\"/tmp/a.b.json\"]\nindent = 99\n";
+
+        match Config::parse(source, "config.toml") {
+            Err(e) => assert!(e.starts_with("config.toml line 3:"), "{}", e),
+            Ok(_) => panic!("an indent of 99 was accepted"),
+        }
+    }
+}
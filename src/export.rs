@@ -0,0 +1,199 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::result::Result;
+use notes;
+use sync;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    MARKDOWN,
+    HTML,
+    OPML,
+}
+
+pub fn get_format_from_str(format: &str) -> Result<ExportFormat, String> {
+    match format.to_lowercase().as_str() {
+        "markdown" | "md" => Ok(ExportFormat::MARKDOWN),
+        "html" => Ok(ExportFormat::HTML),
+        "opml" => Ok(ExportFormat::OPML),
+        _ => Err(format!("Unknown export format {}, use markdown, html or opml", format)),
+    }
+}
+
+//Markdown and OPML are written to the output file, HTML to a directory of pages with index.html for the top level
+pub fn export(root: &notes::Entry, title: &str, format: ExportFormat, output: &str, inline_notes: bool) -> io::Result<()> {
+    match format {
+        ExportFormat::MARKDOWN => write_file(output, export_markdown(root, title).as_str()),
+        ExportFormat::OPML => write_file(output, export_opml(root, title).as_str()),
+        ExportFormat::HTML => export_html(root, title, output, inline_notes),
+    }
+}
+
+pub fn export_markdown(root: &notes::Entry, title: &str) -> String {
+    let mut output = format!("# {}\n\n", title);
+
+    for child in root.get_sorted_children() {
+        markdown_entry(child, 0, &mut output);
+    }
+
+    output
+}
+
+fn markdown_entry(entry: &notes::Entry, depth: usize, output: &mut String) {
+    let indent = "  ".repeat(depth);
+
+    if entry.can_add_child() {
+        output.push_str(format!("{}- **{}**\n", indent, escape_markdown(entry.text.as_str())).as_str());
+    } else if entry.file_location.as_str() != "" {
+        output.push_str(format!("{}- [{}]({})\n", indent, escape_markdown(entry.text.as_str()), escape_link(link_target(entry.file_location.as_str()).as_str())).as_str());
+    } else {
+        output.push_str(format!("{}- {}\n", indent, escape_markdown(entry.text.as_str())).as_str());
+    }
+
+    for child in entry.get_sorted_children() {
+        markdown_entry(child, depth + 1, output);
+    }
+}
+
+pub fn export_opml(root: &notes::Entry, title: &str) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n");
+    output.push_str(format!("  <head>\n    <title>{}</title>\n  </head>\n  <body>\n", escape_xml(title)).as_str());
+
+    for child in root.get_sorted_children() {
+        opml_entry(child, 2, &mut output);
+    }
+
+    output.push_str("  </body>\n</opml>\n");
+
+    output
+}
+
+fn opml_entry(entry: &notes::Entry, depth: usize, output: &mut String) {
+    let indent = "  ".repeat(depth);
+    let mut attributes = format!("text=\"{}\" created=\"{}\"", escape_xml(entry.text.as_str()), entry.time_created.to_rfc2822());
+
+    if entry.file_location.as_str() != "" {
+        if entry.can_add_child() {
+            attributes.push_str(format!(" path=\"{}\"", escape_xml(entry.file_location.as_str())).as_str());
+        } else {
+            attributes.push_str(format!(" type=\"link\" url=\"{}\"", escape_xml(link_target(entry.file_location.as_str()).as_str())).as_str());
+        }
+    }
+
+    let children = entry.get_sorted_children();
+
+    if children.is_empty() {
+        output.push_str(format!("{}<outline {}/>\n", indent, attributes).as_str());
+    } else {
+        output.push_str(format!("{}<outline {}>\n", indent, attributes).as_str());
+
+        for child in children {
+            opml_entry(child, depth + 1, output);
+        }
+
+        output.push_str(format!("{}</outline>\n", indent).as_str());
+    }
+}
+
+fn export_html(root: &notes::Entry, title: &str, output: &str, inline_notes: bool) -> io::Result<()> {
+    fs::create_dir_all(output)?;
+
+    html_page(root, title, &Vec::new(), output, inline_notes)
+}
+
+fn html_page_name(entry: &notes::Entry, is_root: bool) -> String {
+    if is_root {
+        String::from("index.html")
+    } else {
+        format!("category-{}.html", entry.get_id())
+    }
+}
+
+//parents holds the page names and titles leading to this category, used for the breadcrumb
+fn html_page(category: &notes::Entry, title: &str, parents: &Vec<(String, String)>, output: &str, inline_notes: bool) -> io::Result<()> {
+    let is_root = parents.is_empty();
+    let page_title = if is_root { String::from(title) } else { category.text.clone() };
+
+    let mut page = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n", escape_xml(page_title.as_str()));
+
+    if !is_root {
+        let mut breadcrumb = Vec::new();
+        for &(ref page_name, ref parent_title) in parents.iter() {
+            breadcrumb.push(format!("<a href=\"{}\">{}</a>", page_name, escape_xml(parent_title.as_str())));
+        }
+        page.push_str(format!("<nav>{}</nav>\n", breadcrumb.join(" / ")).as_str());
+    }
+
+    page.push_str(format!("<h1>{}</h1>\n<ul>\n", escape_xml(page_title.as_str())).as_str());
+
+    let mut child_parents = parents.clone();
+    child_parents.push((html_page_name(category, is_root), page_title.clone()));
+
+    for child in category.get_sorted_children() {
+        if child.can_add_child() {
+            page.push_str(format!("<li><a href=\"{}\">{}</a></li>\n", html_page_name(child, false), escape_xml(child.text.as_str())).as_str());
+
+            html_page(child, title, &child_parents, output, inline_notes)?;
+        } else if child.file_location.as_str() == "" {
+            page.push_str(format!("<li>{}</li>\n", escape_xml(child.text.as_str())).as_str());
+        } else if inline_notes && sync::is_local_path(child.file_location.as_str()) {
+            let contents = fs::read_to_string(child.file_location.as_str()).unwrap_or(String::from("(this note could not be read)"));
+            page.push_str(format!("<li><h2>{}</h2>\n<pre>{}</pre></li>\n", escape_xml(child.text.as_str()), escape_xml(contents.as_str())).as_str());
+        } else {
+            page.push_str(format!("<li><a href=\"{}\">{}</a></li>\n", escape_xml(link_target(child.file_location.as_str()).as_str()), escape_xml(child.text.as_str())).as_str());
+        }
+    }
+
+    page.push_str("</ul>\n</body>\n</html>\n");
+
+    write_file(Path::new(output).join(html_page_name(category, is_root)).to_string_lossy().as_ref(), page.as_str())
+}
+
+fn link_target(file_location: &str) -> String {
+    if sync::is_local_path(file_location) {
+        format!("file://{}", file_location)
+    } else {
+        String::from(file_location)
+    }
+}
+
+fn write_file(file_name: &str, contents: &str) -> io::Result<()> {
+    let mut file = File::create(file_name)?;
+
+    file.write_all(contents.as_bytes())
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+//Spaces and brackets would end the link early, % is encoded too so the importer can decode it again
+fn escape_link(target: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in target.chars() {
+        if "% ()<>".contains(c) {
+            escaped.push_str(format!("%{:02X}", c as u32).as_str());
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    escaped
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in text.chars() {
+        if "\\`*_[]<>#".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
@@ -0,0 +1,119 @@
+use notes;
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 24;
+const WORD_START_BONUS: i64 = 20;
+const GAP_PENALTY: i64 = 1;
+
+pub struct Candidate {
+    pub id: u32,
+    pub text: String,
+}
+
+//Every entry in the tree, open or not, searchable by its path and tags
+pub fn collect_candidates(root: &notes::Entry) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    collect_children(root, "", &mut candidates);
+
+    candidates
+}
+
+fn collect_children(entry: &notes::Entry, path: &str, candidates: &mut Vec<Candidate>) {
+    for child in entry.get_children() {
+        let child_path = match path {
+            "" => child.text.clone(),
+            _ => format!("{}/{}", path, child.text),
+        };
+
+        let mut text = child_path.clone();
+        for tag in child.get_tags().iter() {
+            text.push_str(format!(" #{}", tag).as_str());
+        }
+
+        candidates.push(Candidate {
+            id: child.get_id(),
+            text: text,
+        });
+
+        collect_children(child, child_path.as_str(), candidates);
+    }
+}
+
+//Returns the indexes of the matching candidates, best match first
+pub fn search(query: &str, candidates: &Vec<Candidate>) -> Vec<usize> {
+    if query.trim().is_empty() {
+        return (0..candidates.len()).collect();
+    }
+
+    let mut matches: Vec<(usize, i64)> = Vec::new();
+
+    for (i, candidate) in candidates.iter().enumerate() {
+        if let Some(score) = score(query, candidate.text.as_str()) {
+            matches.push((i, score));
+        }
+    }
+
+    matches.sort_by(|a, b| b.1.cmp(&a.1).then(candidates[a.0].text.len().cmp(&candidates[b.0].text.len())));
+
+    matches.iter().map(|&(i, _)| i).collect()
+}
+
+//Every word of the query has to appear in order in the text, matches at word starts and runs of matches score higher
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut total = 0;
+
+    for word in query.to_lowercase().split_whitespace() {
+        let word: Vec<char> = word.chars().collect();
+        let mut best = None;
+
+        for start in 0..text.len() {
+            if text[start] != word[0] {
+                continue;
+            }
+
+            if let Some(score) = score_from(&word, &text, start) {
+                if best.map_or(true, |best| score > best) {
+                    best = Some(score);
+                }
+            }
+        }
+
+        total += best?;
+    }
+
+    Some(total)
+}
+
+fn score_from(word: &Vec<char>, text: &Vec<char>, start: usize) -> Option<i64> {
+    let mut score = 0;
+    let mut position = start;
+    let mut last_match: Option<usize> = None;
+
+    for &c in word.iter() {
+        while position < text.len() && text[position] != c {
+            position += 1;
+        }
+
+        if position >= text.len() {
+            return None;
+        }
+
+        score += MATCH_SCORE;
+
+        if position == 0 || !text[position - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+
+        match last_match {
+            Some(last) if last + 1 == position => score += CONSECUTIVE_BONUS,
+            Some(last) => score -= GAP_PENALTY * (position - last - 1) as i64,
+            None => (),
+        }
+
+        last_match = Some(position);
+        position += 1;
+    }
+
+    Some(score)
+}
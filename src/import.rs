@@ -0,0 +1,172 @@
+extern crate glob;
+
+use self::glob::Pattern;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::result::Result;
+use std::time::UNIX_EPOCH;
+use notes;
+
+pub struct ImportOptions {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+    pub max_depth: Option<usize>,
+}
+
+impl ImportOptions {
+    pub fn new() -> ImportOptions {
+        ImportOptions {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: None,
+        }
+    }
+
+    pub fn add_include(&mut self, pattern: &str) -> Result<(), String> {
+        self.include.push(parse_pattern(pattern)?);
+
+        Ok(())
+    }
+
+    pub fn add_exclude(&mut self, pattern: &str) -> Result<(), String> {
+        self.exclude.push(parse_pattern(pattern)?);
+
+        Ok(())
+    }
+
+    //Patterns are tried against both the name and the path relative to the imported directory
+    fn matches(patterns: &Vec<Pattern>, name: &str, relative_path: &str) -> bool {
+        patterns.iter().any(|pattern| pattern.matches(name) || pattern.matches(relative_path))
+    }
+}
+
+//Builds a category for the directory with categories for subdirectories and notes for files. Nothing is added to the notebook here
+pub fn import_directory(directory: &str, options: &ImportOptions, next_id: &mut u32) -> io::Result<notes::Entry> {
+    let path = fs::canonicalize(directory)?;
+
+    if !path.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a directory", directory)));
+    }
+
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_string_lossy().into_owned(),
+    };
+
+    let mut category = build_entry(&path, name.as_str(), true, next_id);
+    import_children(&mut category, &path, "", options, 1, next_id)?;
+
+    Ok(category)
+}
+
+fn import_children(category: &mut notes::Entry, directory: &Path, relative_directory: &str, options: &ImportOptions, depth: usize, next_id: &mut u32) -> io::Result<()> {
+    let mut dir_entries = Vec::new();
+    for dir_entry in fs::read_dir(directory)? {
+        dir_entries.push(dir_entry?);
+    }
+    dir_entries.sort_by_key(|dir_entry| dir_entry.file_name());
+
+    for dir_entry in dir_entries {
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        let relative_path = format!("{}{}", relative_directory, name);
+
+        if ImportOptions::matches(&options.exclude, name.as_str(), relative_path.as_str()) {
+            continue;
+        }
+
+        //Symlinked directories are not followed so loops cannot happen
+        let file_type = dir_entry.file_type()?;
+
+        if file_type.is_dir() {
+            if options.max_depth.map_or(false, |max_depth| depth >= max_depth) {
+                continue;
+            }
+
+            let mut child = build_entry(&dir_entry.path(), name.as_str(), true, next_id);
+            import_children(&mut child, &dir_entry.path(), format!("{}/", relative_path).as_str(), options, depth + 1, next_id)?;
+
+            //With include patterns only directories that lead to an included file are kept
+            if options.include.is_empty() || child.num_children() > 0 {
+                category.add_child(child);
+            }
+        } else if options.include.is_empty() || ImportOptions::matches(&options.include, name.as_str(), relative_path.as_str()) {
+            category.add_child(build_entry(&dir_entry.path(), name.as_str(), false, next_id));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn build_entry(path: &Path, name: &str, is_category: bool, next_id: &mut u32) -> notes::Entry {
+    let mut file_location = path.to_string_lossy().into_owned();
+    if is_category {
+        file_location.push('/');
+    }
+
+    let mut builder = notes::EntryBuilder::new(*next_id).set_text(name).set_is_category(is_category).set_full_file_location(file_location).set_create_directories(false);
+    *next_id += 1;
+
+    if let Ok(metadata) = fs::metadata(path) {
+        if let Ok(created) = metadata.created().or(metadata.modified()) {
+            if let Ok(duration) = created.duration_since(UNIX_EPOCH) {
+                builder = builder.set_time_created(duration.as_secs() as i64);
+            }
+        }
+    }
+
+    builder.build()
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern, String> {
+    match Pattern::new(pattern) {
+        Ok(pattern) => Ok(pattern),
+        Err(e) => Err(format!("invalid pattern {}: {}", pattern, e)),
+    }
+}
+
+//One line per entry, indented to show the tree, used for dry runs
+pub fn preview(entry: &notes::Entry) -> Vec<String> {
+    let mut lines = Vec::new();
+    preview_entry(entry, 0, &mut lines);
+
+    lines
+}
+
+fn preview_entry(entry: &notes::Entry, depth: usize, lines: &mut Vec<String>) {
+    let suffix = if entry.can_add_child() { "/" } else { "" };
+    lines.push(format!("{}{}{}", "  ".repeat(depth), entry.text, suffix));
+
+    for child in entry.get_children() {
+        preview_entry(child, depth + 1, lines);
+    }
+}
+
+pub fn count_entries(entry: &notes::Entry) -> usize {
+    1 + entry.get_children().iter().map(|child| count_entries(child)).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn imports_non_ascii_names() {
+        let directory = env::temp_dir().join(format!("note_manager_import_{}", process::id()));
+        fs::create_dir_all(directory.join("données")).unwrap();
+        fs::write(directory.join("café"), "").unwrap();
+        fs::write(directory.join("données").join("日本語"), "").unwrap();
+
+        let mut next_id = 1;
+        let category = import_directory(directory.to_str().unwrap(), &ImportOptions::new(), &mut next_id);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let mut category = category.unwrap();
+        category.open_levels(2);
+        let names: Vec<String> = category.flatten_children().iter().map(|entry| notes::get_file_name(&entry.file_location)).collect();
+        assert_eq!(names, vec!["café", "données/", "日本語"]);
+        assert_eq!(count_entries(&category), 4);
+    }
+}
@@ -0,0 +1,431 @@
+extern crate json;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::result::Result;
+use std::time::UNIX_EPOCH;
+use self::json::object;
+use notes;
+use search;
+use sync;
+
+const INDEX_VERSION: u32 = 3;
+//Bigger files are most likely not notes someone wrote, and would only bloat the index
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+//Longer words are mostly hashes or encoded data, and every suffix of them would be stored
+const MAX_TERM_LENGTH: usize = 64;
+
+struct IndexedFile {
+    file: String,
+    modified: u64,
+    //Binary and oversized files are remembered so they aren't read again until they change
+    is_text: bool,
+    //The lines each word is on
+    terms: HashMap<String, Vec<usize>>,
+}
+
+//Maps the words in every note file to the notes they appear in, kept in <notebook>.index
+pub struct ContentIndex {
+    file_name: String,
+    files: HashMap<u32, IndexedFile>,
+    postings: HashMap<String, HashSet<u32>>,
+    //Every suffix of every word, so words containing a query word are found without going through all of them
+    suffixes: BTreeMap<String, HashSet<String>>,
+    changed: bool,
+}
+
+pub fn index_file_name(file_name: &str) -> String {
+    format!("{}.index", file_name)
+}
+
+impl ContentIndex {
+    pub fn new(file_name: &str) -> ContentIndex {
+        ContentIndex {
+            file_name: String::from(file_name),
+            files: HashMap::new(),
+            postings: HashMap::new(),
+            suffixes: BTreeMap::new(),
+            changed: true,
+        }
+    }
+
+    //A missing or unreadable index just starts out empty and gets rebuilt by the next update
+    pub fn load(file_name: &str) -> ContentIndex {
+        let mut index = ContentIndex::new(file_name);
+
+        if let Ok(files) = read_index(file_name) {
+            for (id, indexed_file) in files {
+                index.insert(id, indexed_file);
+            }
+
+            index.changed = false;
+        }
+
+        index
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let mut json_files = json::JsonValue::new_object();
+        for (id, indexed_file) in self.files.iter() {
+            let mut json_terms = json::JsonValue::new_object();
+            for (term, lines) in indexed_file.terms.iter() {
+                json_terms[term.as_str()] = lines.clone().into();
+            }
+
+            json_files[id.to_string().as_str()] = object! {
+                "file" => indexed_file.file.as_str(),
+                "modified" => indexed_file.modified,
+                "text" => indexed_file.is_text,
+                "terms" => json_terms,
+            };
+        }
+
+        let json_output = object! {
+            "version" => INDEX_VERSION,
+            "files" => json_files,
+        };
+
+        let temp_file_name = format!("{}.tmp", self.file_name);
+        {
+            let mut temp_file = File::create(temp_file_name.as_str())?;
+            temp_file.write_all(json::stringify(json_output).as_bytes())?;
+        }
+        fs::rename(temp_file_name.as_str(), self.file_name.as_str())?;
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.files.len()
+    }
+
+    //Reindexes notes that are new, were moved to another file or have a different mtime, and drops notes that are gone
+    pub fn update(&mut self, root: &notes::Entry) {
+        let mut seen = HashSet::new();
+        let mut notes = Vec::new();
+        collect_notes(root, &mut notes);
+
+        for (id, file) in notes {
+            seen.insert(id);
+            self.update_note(id, file);
+        }
+
+        let removed: Vec<u32> = self.files.keys().filter(|id| !seen.contains(id)).cloned().collect();
+        for id in removed {
+            self.remove(id);
+        }
+    }
+
+    //Only looks at the notes under an entry that was added, deleted, moved, opened or pointed at another file
+    pub fn update_entry(&mut self, root: &notes::Entry, id: u32) {
+        let entry = match root.get_child_by_id(id) {
+            Some(entry) => entry,
+            None => {
+                self.remove(id);
+                return;
+            },
+        };
+
+        let mut notes = Vec::new();
+        if is_live(root, id) {
+            if is_note(entry) {
+                notes.push((entry.get_id(), entry.file_location.clone()));
+            }
+            collect_notes(entry, &mut notes);
+        }
+
+        let mut ids = vec![id];
+        collect_ids(entry, &mut ids);
+
+        for id in ids {
+            if !notes.iter().any(|&(note_id, _)| note_id == id) {
+                self.remove(id);
+            }
+        }
+
+        for (id, file) in notes {
+            self.update_note(id, file);
+        }
+    }
+
+    fn update_note(&mut self, id: u32, file: String) {
+        let modified = match file_modified(file.as_str()) {
+            Some(modified) => modified,
+            None => {
+                self.remove(id);
+                return;
+            },
+        };
+
+        let up_to_date = match self.files.get(&id) {
+            Some(indexed_file) => indexed_file.file == file && indexed_file.modified == modified,
+            None => false,
+        };
+
+        if !up_to_date {
+            self.remove(id);
+
+            let (is_text, terms) = match read_text(file.as_str()) {
+                Ok(Some(contents)) => (true, find_terms(contents.as_str())),
+                Ok(None) => (false, HashMap::new()),
+                Err(_) => return,
+            };
+
+            self.insert(id, IndexedFile {
+                file: file,
+                modified: modified,
+                is_text: is_text,
+                terms: terms,
+            });
+        }
+    }
+
+    //Regexes, queries without words and queries with words too long to be indexed can't use the terms,
+    //so they go through every line of every note in notebook order
+    pub fn search(&self, root: &notes::Entry, query: &search::Query) -> Vec<search::SearchResult> {
+        let query_terms = match query.get_literal() {
+            Some(literal) => tokenize(literal),
+            None => Vec::new(),
+        };
+
+        if query_terms.is_empty() || query_terms.iter().any(|term| term.chars().count() > MAX_TERM_LENGTH) {
+            let mut notes = Vec::new();
+            collect_notes(root, &mut notes);
+
+            let mut results = Vec::new();
+            for (id, _) in notes {
+                if let Some(indexed_file) = self.files.get(&id).filter(|indexed_file| indexed_file.is_text) {
+                    if let Ok(Some(contents)) = read_text(indexed_file.file.as_str()) {
+                        for (i, line) in contents.lines().enumerate() {
+                            search::search_line(id, indexed_file.file.as_str(), i + 1, line, query, &mut results);
+                        }
+                    }
+                }
+            }
+
+            return results;
+        }
+
+        //A query word can be part of a longer word, so every term containing it counts
+        let mut scores: Option<HashMap<u32, f64>> = None;
+        let mut candidate_lines: HashMap<u32, HashSet<usize>> = HashMap::new();
+
+        for query_term in query_terms.iter() {
+            let mut term_lines: HashMap<u32, HashSet<usize>> = HashMap::new();
+
+            for term in self.find_terms_containing(query_term.as_str()) {
+                for id in self.postings[term].iter() {
+                    let lines = term_lines.entry(*id).or_insert(HashSet::new());
+                    lines.extend(self.files[id].terms[term].iter().cloned());
+                }
+            }
+
+            let weight = ((self.files.len() as f64 + 1.0) / (term_lines.len() as f64 + 1.0)).ln() + 1.0;
+
+            scores = Some(match scores {
+                None => term_lines.iter().map(|(id, lines)| (*id, (1.0 + (lines.len() as f64).ln()) * weight)).collect(),
+                Some(scores) => scores.into_iter().filter_map(|(id, score)| term_lines.get(&id).map(|lines| (id, score + (1.0 + (lines.len() as f64).ln()) * weight))).collect(),
+            });
+
+            //A line can only match if every word of the query is on it
+            for (id, lines) in term_lines {
+                let candidates = match candidate_lines.remove(&id) {
+                    Some(candidates) => candidates.intersection(&lines).cloned().collect(),
+                    None => lines,
+                };
+                candidate_lines.insert(id, candidates);
+            }
+        }
+
+        let mut ranked: Vec<(u32, f64)> = scores.unwrap_or(HashMap::new()).into_iter().collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+
+        //The words can all be on a line without being next to each other, so the candidate lines are read back and checked
+        let mut results = Vec::new();
+        for (id, _) in ranked {
+            let indexed_file = &self.files[&id];
+            let contents = match read_text(indexed_file.file.as_str()) {
+                Ok(Some(contents)) => contents,
+                _ => continue,
+            };
+            let lines: Vec<&str> = contents.lines().collect();
+
+            let mut candidates: Vec<usize> = candidate_lines[&id].iter().cloned().collect();
+            candidates.sort();
+
+            for i in candidates {
+                if let Some(line) = lines.get(i) {
+                    search::search_line(id, indexed_file.file.as_str(), i + 1, line, query, &mut results);
+                }
+            }
+        }
+
+        results
+    }
+
+    fn find_terms_containing(&self, query_term: &str) -> HashSet<&String> {
+        self.suffixes.range(String::from(query_term)..)
+            .take_while(|&(suffix, _)| suffix.starts_with(query_term))
+            .flat_map(|(_, terms)| terms.iter())
+            .collect()
+    }
+
+    fn insert(&mut self, id: u32, indexed_file: IndexedFile) {
+        for term in indexed_file.terms.keys() {
+            if !self.postings.contains_key(term) {
+                for (i, _) in term.char_indices() {
+                    self.suffixes.entry(String::from(&term[i..])).or_insert(HashSet::new()).insert(term.clone());
+                }
+            }
+
+            self.postings.entry(term.clone()).or_insert(HashSet::new()).insert(id);
+        }
+
+        self.files.insert(id, indexed_file);
+        self.changed = true;
+    }
+
+    fn remove(&mut self, id: u32) {
+        if let Some(indexed_file) = self.files.remove(&id) {
+            for term in indexed_file.terms.keys() {
+                let is_empty = match self.postings.get_mut(term) {
+                    Some(postings) => {
+                        postings.remove(&id);
+                        postings.is_empty()
+                    },
+                    None => false,
+                };
+
+                if is_empty {
+                    self.postings.remove(term);
+
+                    for (i, _) in term.char_indices() {
+                        let suffix = &term[i..];
+                        let no_terms_left = match self.suffixes.get_mut(suffix) {
+                            Some(terms) => {
+                                terms.remove(term);
+                                terms.is_empty()
+                            },
+                            None => false,
+                        };
+
+                        if no_terms_left {
+                            self.suffixes.remove(suffix);
+                        }
+                    }
+                }
+            }
+
+            self.changed = true;
+        }
+    }
+}
+
+fn read_index(file_name: &str) -> Result<Vec<(u32, IndexedFile)>, String> {
+    let mut contents = String::new();
+    File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents)).map_err(|e| e.to_string())?;
+
+    let contents_json = json::parse(contents.as_str()).map_err(|e| e.to_string())?;
+    if contents_json["version"].as_u32() != Some(INDEX_VERSION) {
+        return Err(String::from("unsupported index version"));
+    }
+
+    let mut files = Vec::new();
+    for (id, json_file) in contents_json["files"].entries() {
+        let id = id.parse::<u32>().map_err(|e| e.to_string())?;
+
+        let file = json_file["file"].as_str().ok_or("file is missing")?;
+        let modified = json_file["modified"].as_u64().ok_or("modified is missing")?;
+        let is_text = json_file["text"].as_bool().ok_or("text is missing")?;
+
+        let mut terms = HashMap::new();
+        for (term, json_lines) in json_file["terms"].entries() {
+            let mut term_lines = Vec::new();
+            for line in json_lines.members() {
+                term_lines.push(line.as_usize().ok_or("term line is missing")?);
+            }
+
+            terms.insert(String::from(term), term_lines);
+        }
+
+        files.push((id, IndexedFile {
+            file: String::from(file),
+            modified: modified,
+            is_text: is_text,
+            terms: terms,
+        }));
+    }
+
+    Ok(files)
+}
+
+fn collect_notes(entry: &notes::Entry, notes: &mut Vec<(u32, String)>) {
+    for child in entry.get_children() {
+        if is_note(child) {
+            notes.push((child.get_id(), child.file_location.clone()));
+        }
+
+        collect_notes(child, notes);
+    }
+}
+
+fn collect_ids(entry: &notes::Entry, ids: &mut Vec<u32>) {
+    for child in entry.get_children() {
+        ids.push(child.get_id());
+        collect_ids(child, ids);
+    }
+}
+
+fn is_note(entry: &notes::Entry) -> bool {
+    !entry.can_add_child() && sync::is_local_path(entry.file_location.as_str())
+}
+
+//Deleted entries are kept around for undo, only the ones still reachable from the root count
+fn is_live(entry: &notes::Entry, id: u32) -> bool {
+    entry.get_id() == id || entry.get_children().iter().any(|child| is_live(child, id))
+}
+
+//Milliseconds since the epoch, small enough to survive being stored as a JSON number
+fn file_modified(file: &str) -> Option<u64> {
+    let modified = fs::metadata(file).and_then(|metadata| metadata.modified()).ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+
+    Some(since_epoch.as_secs() * 1000 + since_epoch.subsec_millis() as u64)
+}
+
+//Gives None for files that are too big or aren't UTF-8, those are left out of the index
+fn read_text(file: &str) -> io::Result<Option<String>> {
+    if fs::metadata(file)?.len() > MAX_FILE_SIZE {
+        return Ok(None);
+    }
+
+    Ok(String::from_utf8(fs::read(file)?).ok())
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(|word| word.to_lowercase()).collect()
+}
+
+fn find_terms(contents: &str) -> HashMap<String, Vec<usize>> {
+    let mut terms: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        for term in tokenize(line).into_iter().filter(|term| term.chars().count() <= MAX_TERM_LENGTH) {
+            let term_lines = terms.entry(term).or_insert(Vec::new());
+            if term_lines.last() != Some(&i) {
+                term_lines.push(i);
+            }
+        }
+    }
+
+    terms
+}
@@ -1,24 +1,47 @@
 use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::result::Result;
+use std::time::{Duration, Instant};
 use note_manager::*;
-use pancurses::*;
+use signals;
+use keys::*;
 
 #[derive(Clone)]
 pub enum KeybindMode {
     DEFAULT,
-    MULTIKEY(String),
-    MOVE(u32),
+    MULTIKEY(Vec<Key>),
+    MOVE(Vec<u32>),
     ALL
 }
 
+//The count is the number typed before the keys, if there was one
+pub type KeybindFunction = Rc<Fn(&mut NoteManager, &KeybindMode, Option<usize>) -> Option<KeybindMode>>;
+
+const MAX_COUNT: usize = 99999;
+//getch wakes up this often so autosave and signals are handled while idle
+const INPUT_TIMEOUT: i32 = 1000;
+
+pub struct Command {
+    pub description: String,
+    function: KeybindFunction,
+}
+
 pub struct Keybind {
     pub mode: KeybindMode,
-    pub function: Box<FnMut(&mut NoteManager, &KeybindMode) -> Option<KeybindMode>>,
+    pub command: String,
+    function: KeybindFunction,
 }
 
 pub struct KeybindManager {
     nm: NoteManager,
     mode: KeybindMode,
-    keybindings: HashMap<String, Vec<Keybind>>,
+    keybindings: HashMap<Vec<Key>, Vec<Keybind>>,
+    commands: HashMap<String, Command>,
+    count: Option<usize>,
+    key_timeout: Option<Duration>,
+    pending_since: Instant,
+    showing_hints: bool,
 }
 
 impl KeybindManager {
@@ -27,83 +50,340 @@ impl KeybindManager {
             nm: nm,
             mode: KeybindMode::DEFAULT,
             keybindings: HashMap::new(),
+            commands: HashMap::new(),
+            count: None,
+            key_timeout: None,
+            pending_since: Instant::now(),
+            showing_hints: false,
         }
     }
 
-    pub fn add<F: 'static>(&mut self, keys: &str, mode: KeybindMode, function: F) where
-        F: FnMut(&mut NoteManager, &KeybindMode) -> Option<KeybindMode>
+    //Commands are registered by name so the config can bind keys to them
+    pub fn register<F: 'static>(&mut self, name: &str, description: &str, function: F) where
+        F: Fn(&mut NoteManager, &KeybindMode, Option<usize>) -> Option<KeybindMode>
     {
-        let keybind = Keybind::new(mode, function);
-        if self.keybindings.get_mut(&String::from(keys)).is_none() {
-            self.keybindings.insert(String::from(keys), vec![keybind]); 
-        } else {
-            self.keybindings.get_mut(&String::from(keys)).unwrap().push(keybind);
-        }
+        self.commands.insert(String::from(name), Command {
+            description: String::from(description),
+            function: Rc::new(function),
+        });
     }
 
-    pub fn handle_input(&mut self, key: char) {
-        let mut keybind = String::new();
+    //Without a timeout a sequence that is also the start of a longer one waits for the next key
+    pub fn set_key_timeout(&mut self, key_timeout: Option<Duration>) {
+        self.key_timeout = key_timeout;
+    }
 
-        match self.mode {
-            KeybindMode::MULTIKEY(ref prev_keys) => {
-                keybind = prev_keys.clone();
-                keybind.push(key);
-            },
-            _ => {
-                keybind.push(key); 
+    //Binding keys that are already bound in the same mode replaces the old command
+    pub fn bind(&mut self, keys: &[Key], mode: KeybindMode, command: &str) -> Result<(), String> {
+        let function = match self.commands.get(command) {
+            Some(command) => command.function.clone(),
+            None => return Err(format!("unknown command {}", command)),
+        };
+
+        if keys.is_empty() {
+            return Err(String::from("no keys given"));
+        }
+
+        if let Some('1'..='9') = keys[0].get_char() {
+            return Err(String::from("keys can't start with 1-9, those are read as a count"));
+        }
+
+        if is_same_mode(&mode, &KeybindMode::MOVE(Vec::new())) && keys.len() > 1 {
+            return Err(String::from("move mode only takes single keys"));
+        }
+
+        let mode = binding_mode(keys, mode);
+
+        self.unbind(keys, &mode);
+        self.keybindings.entry(keys.to_vec()).or_insert(Vec::new()).push(Keybind::new(mode, command, function));
+
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, keys: &[Key], mode: &KeybindMode) {
+        let is_empty = match self.keybindings.get_mut(keys) {
+            Some(keybinding) => {
+                keybinding.retain(|k| !is_same_mode(&k.mode, mode));
+                keybinding.is_empty()
             },
+            None => false,
+        };
+
+        if is_empty {
+            self.keybindings.remove(keys);
+        }
+    }
+
+    //Applies the [keys] tables from the config, anything that can't be bound or clashes is shown as a message
+    pub fn load_keymap(&mut self, keymap: &[(KeybindMode, Vec<Key>, String)]) {
+        let mut problems = Vec::new();
+        let mut bound: Vec<(KeybindMode, Vec<Key>, String)> = Vec::new();
+
+        for &(ref mode, ref keys, ref command) in keymap.iter() {
+            let mode = binding_mode(keys, mode.clone());
+
+            //Two notations for the same keys, like <CR> and <Enter>, can end up bound twice
+            if let Some(&(_, _, ref other)) = bound.iter().find(|&&(ref m, ref k, _)| is_same_mode(m, &mode) && k == keys) {
+                if other != command {
+                    problems.push(format!("{} is bound to both {} and {}", format_keys(keys), other, command));
+                }
+            }
+
+            //none removes a default binding
+            let result = match command.as_str() {
+                "none" => {
+                    self.unbind(keys, &mode);
+                    Ok(())
+                },
+                _ => self.bind(keys, mode.clone(), command.as_str()),
+            };
+
+            match result {
+                Ok(_) => bound.push((mode, keys.clone(), command.clone())),
+                Err(e) => problems.push(format!("{}: {}", format_keys(keys), e)),
+            }
+        }
+
+        problems.extend(self.find_conflicts());
+
+        match problems.len() {
+            0 => (),
+            1 => self.nm.set_message(format!("Key binding problem: {}", problems[0]).as_str()),
+            n => self.nm.set_message(format!("{} key binding problems: {}", n, problems.join("; ")).as_str()),
+        }
+    }
+
+    //Without a timeout a sequence that starts a longer one has to wait for another key
+    pub fn find_conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        let mut all_keys: Vec<&Vec<Key>> = self.keybindings.keys().collect();
+        all_keys.sort();
+
+        for keys in all_keys.iter() {
+            let keybinding = &self.keybindings[*keys];
+
+            let shadowed: Vec<String> = all_keys.iter().filter(|longer| is_partial_keybind(keys, longer)).map(|longer| format_keys(longer)).collect();
+
+            if let Some(k) = keybinding.iter().find(|k| !is_same_mode(&k.mode, &KeybindMode::MOVE(Vec::new()))) {
+                if !shadowed.is_empty() && self.key_timeout.is_none() {
+                    conflicts.push(format!("{} ({}) shadows {}, set key_timeout to run it on its own", format_keys(keys), k.command, shadowed.join(", ")));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    pub fn handle_input(&mut self, key: Key) {
+        //The hints are drawn over the notes, so they are cleared before anything else happens
+        if self.showing_hints {
+            self.nm.render();
+            self.showing_hints = false;
+        }
+
+        let pending = match self.mode {
+            KeybindMode::MULTIKEY(ref keys) => Some(keys.clone()),
+            _ => None,
+        };
+
+        //Escape drops a count or an unfinished sequence
+        if key.is(KeyCode::ESCAPE) && (pending.is_some() || self.count.is_some()) {
+            self.mode = KeybindMode::DEFAULT;
+            self.count = None;
+            return;
+        }
+
+        //Digits before a key sequence are a count, a 0 only counts after another digit
+        if pending.is_none() {
+            if let Some(digit) = key.get_char().and_then(|c| c.to_digit(10)).filter(|&digit| digit > 0 || self.count.is_some()) {
+                self.count = Some((self.count.unwrap_or(0) * 10 + digit as usize).min(MAX_COUNT));
+                return;
+            }
+        }
+
+        let mut keybind = pending.clone().unwrap_or(Vec::new());
+        keybind.push(key.clone()); 
+
+        //Longer sequences are only bound in the default mode, so move mode never waits
+        let is_move = is_same_mode(&self.mode, &KeybindMode::MOVE(Vec::new()));
+        if !is_move && self.keybindings.keys().any(|hash_map_key| is_partial_keybind(&keybind, hash_map_key)) {
+            self.mode = KeybindMode::MULTIKEY(keybind); 
+            self.pending_since = Instant::now();
+            return;
         }
 
+        //The keys so far are a binding of their own that was waiting to see if a longer one was meant
+        if let Some(pending) = pending {
+            if !self.keybindings.contains_key(&keybind) && self.keybindings.contains_key(&pending) {
+                self.run_keybind(&pending);
+                self.handle_input(key);
+                return;
+            }
+        }
+
+        self.run_keybind(&keybind);
+    }
+
+    fn run_keybind(&mut self, keys: &[Key]) {
+        let mode = match self.mode {
+            KeybindMode::MULTIKEY(_) if keys.len() > 1 => KeybindMode::MULTIKEY(Vec::new()),
+            KeybindMode::MULTIKEY(_) => KeybindMode::DEFAULT,
+            ref mode => mode.clone(),
+        };
+
         let mut new_mode = KeybindMode::DEFAULT;
 
-        match self.keybindings.get_mut(&keybind) {
-            Some(keybinding) => {  
-                for k in keybinding {
-                    if self.mode == k.mode { 
-                        match (k.function)(&mut self.nm, &self.mode) {
-                            Some(m) => new_mode = m,
-                            None => new_mode = self.mode.clone(),
-                        }
-                    }
+        //A short key timeout would carry into the prompts a command opens
+        self.nm.window().timeout(INPUT_TIMEOUT);
+
+        if let Some(function) = self.find_keybind(keys, &mode).map(|k| k.function.clone()) {
+            match function(&mut self.nm, &mode, self.count) {
+                Some(m) => new_mode = m,
+                None => new_mode = mode.clone(),
+            }
+        }
+
+        self.count = None;
+        self.mode = new_mode;
+    }
+
+    //Keys bound in the mode itself shadow the same keys bound in every mode
+    fn find_keybind(&self, keys: &[Key], mode: &KeybindMode) -> Option<&Keybind> {
+        let keybinding = self.keybindings.get(keys)?;
+
+        keybinding.iter().find(|k| is_same_mode(&k.mode, mode)).or_else(|| keybinding.iter().find(|k| *mode == k.mode))
+    }
+
+    //Runs a sequence that is also the start of a longer one once the timeout is up
+    fn check_key_timeout(&mut self) {
+        let pending = match self.mode {
+            KeybindMode::MULTIKEY(ref keys) if self.keybindings.contains_key(keys) => keys.clone(),
+            _ => return,
+        };
+
+        if let Some(key_timeout) = self.key_timeout {
+            if self.pending_since.elapsed() >= key_timeout {
+                if self.showing_hints {
+                    self.nm.render();
+                    self.showing_hints = false;
                 }
+
+                self.run_keybind(&pending);
+                self.show_pending_keys();
+                self.nm.render_title();
+            }
+        }
+    }
+
+    //How long getch waits, short enough to notice the key timeout
+    //Only a pending sequence that is a binding of its own has anything to run when the timeout is up
+    fn get_input_timeout(&self) -> i32 {
+        match (self.key_timeout, &self.mode) {
+            (Some(key_timeout), KeybindMode::MULTIKEY(ref pending)) if self.keybindings.contains_key(pending) => {
+                let remaining = key_timeout.checked_sub(self.pending_since.elapsed()).unwrap_or(Duration::from_millis(0));
+                (remaining.as_secs() * 1000 + remaining.subsec_millis() as u64).min(INPUT_TIMEOUT as u64) as i32
             },
-            None => (),
+            _ => INPUT_TIMEOUT,
+        }
+    }
+
+    //Shows the count and keys typed so far and what can still follow them
+    fn show_pending_keys(&mut self) {
+        let pending = match self.mode {
+            KeybindMode::MULTIKEY(ref keys) => keys.clone(),
+            _ => Vec::new(),
+        };
+
+        let count = self.count.map_or(String::new(), |count| count.to_string());
+        self.nm.set_pending_keys(format!("{}{}", count, format_keys(&pending)).as_str());
+
+        if !pending.is_empty() {
+            let hints = self.get_hints(&pending);
+            self.nm.show_key_hints(format_keys(&pending).as_str(), &hints);
+            self.showing_hints = true;
         }
+    }
+
+    //The keys that can come next and the commands they finish, sorted by key
+    pub fn get_hints(&self, pending: &[Key]) -> Vec<(String, String)> {
+        let mut hints: Vec<(String, String)> = Vec::new();
+
+        for keys in self.keybindings.keys() {
+            if !is_partial_keybind(pending, keys) {
+                continue;
+            }
 
-        for hash_map_key in self.keybindings.keys() {
-            if is_partial_keybind(&keybind, hash_map_key) {
-                new_mode = KeybindMode::MULTIKEY(keybind); 
-                break;
+            if let Some(k) = self.find_keybind(keys, &KeybindMode::MULTIKEY(Vec::new())) {
+                let description = self.commands.get(&k.command).map_or(String::new(), |command| command.description.clone());
+                hints.push((format_keys(&keys[pending.len()..]), description));
             }
         }
 
-        self.mode = new_mode;
+        hints.sort();
+        hints
+    }
+
+    //Terminals send Alt with a key as Escape right before it, a key that is already waiting after Escape was typed with it
+    fn read_key(&mut self) -> Option<Key> {
+        let key = Key::from_input(self.nm.window().getch()?);
+
+        if key.is(KeyCode::ESCAPE) {
+            self.nm.window().nodelay(true);
+            let next = self.nm.window().getch();
+            self.nm.window().timeout(self.get_input_timeout());
+
+            if let Some(input) = next {
+                return Some(Key::from_input(input).set_alt());
+            }
+        }
+
+        Some(key)
     }
 
     pub fn begin(&mut self) {
         self.nm.render();
         while self.nm.running {
-            match self.nm.window.getch() {
-                Some(Input::Character(input)) => {
-                    self.handle_input(input);
+            self.nm.window().timeout(self.get_input_timeout());
+
+            match self.read_key() {
+                //Nothing is bound to a resize, redrawing keeps any pending keys
+                Some(ref key) if key.is(KeyCode::RESIZE) => {
+                    self.nm.resize();
+                    self.show_pending_keys();
+                    self.nm.render_title();
+                },
+                Some(key) => {
+                    self.nm.clear_message();
+                    self.handle_input(key);
+                    self.show_pending_keys();
+                    self.nm.render_title();
                 },
-                _ => (),
+                None => self.check_key_timeout(),
+            }
+
+            if signals::take_suspend_request() {
+                self.nm.suspend();
+            }
+
+            if signals::exit_requested() {
+                self.nm.flush();
+            } else if self.nm.running {
+                self.nm.autosave();
             }
         }
     }
 
     pub fn end(&mut self) {
-        self.nm.end(); 
+        self.nm.close(); 
     }
 }
 
 impl Keybind {
-    pub fn new<F: 'static>(mode: KeybindMode, function: F) -> Keybind where
-        F: FnMut(&mut NoteManager, &KeybindMode) -> Option<KeybindMode>
-    {
+    pub fn new(mode: KeybindMode, command: &str, function: KeybindFunction) -> Keybind {
         Keybind {
             mode: mode,
-            function: Box::new(function),
+            command: String::from(command),
+            function: function,
         } 
     }
 }
@@ -121,10 +401,32 @@ impl PartialEq for KeybindMode {
     }
 }
 
-fn is_partial_keybind(keys1: &String, keys2: &String) -> bool {
+//The keys after the first one arrive while waiting for the rest of the sequence
+fn binding_mode(keys: &[Key], mode: KeybindMode) -> KeybindMode {
+    match mode {
+        KeybindMode::DEFAULT if keys.len() > 1 => KeybindMode::MULTIKEY(Vec::new()),
+        mode => mode,
+    }
+}
+
+//Unlike ==, ALL only matches ALL
+fn is_same_mode(mode1: &KeybindMode, mode2: &KeybindMode) -> bool {
+    mem::discriminant(mode1) == mem::discriminant(mode2)
+}
+
+pub fn get_mode_from_str(mode: &str) -> Option<KeybindMode> {
+    match mode {
+        "default" => Some(KeybindMode::DEFAULT),
+        "move" => Some(KeybindMode::MOVE(Vec::new())),
+        "all" => Some(KeybindMode::ALL),
+        _ => None,
+    }
+}
+
+fn is_partial_keybind(keys1: &[Key], keys2: &[Key]) -> bool {
     if keys1 == keys2 {
         return false; 
     }
 
-    keys2.starts_with(keys1.as_str())
+    keys2.starts_with(keys1)
 } 
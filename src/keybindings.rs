@@ -1,60 +1,337 @@
 use keybind_manager;
+use keys;
 use actions::*;
 use notes;
 use note_manager;
+use backups;
+use import;
+use export;
+use outline;
+use tags;
+use search;
+use std::fs;
 
 pub fn init_keybindings(kbm: &mut keybind_manager::KeybindManager) {
-    kbm.add("d", keybind_manager::KeybindMode::DEFAULT, delete_entry);
-    kbm.add("an", keybind_manager::KeybindMode::MULTIKEY(String::from("")), add_note);
-    kbm.add("j", keybind_manager::KeybindMode::ALL, cursor_down);
-    kbm.add("k", keybind_manager::KeybindMode::ALL, cursor_up);
-    kbm.add("u", keybind_manager::KeybindMode::DEFAULT, undo);
-    kbm.add("al", keybind_manager::KeybindMode::MULTIKEY(String::from("")), redo);
-    kbm.add("\n", keybind_manager::KeybindMode::DEFAULT, select);
-    kbm.add("q", keybind_manager::KeybindMode::DEFAULT, close);
-    kbm.add("m", keybind_manager::KeybindMode::DEFAULT, move_start);
-    kbm.add("\n", keybind_manager::KeybindMode::MOVE(0), move_complete);
-    kbm.add("ac", keybind_manager::KeybindMode::MULTIKEY(String::from("")), add_category);
-    kbm.add("ar", keybind_manager::KeybindMode::MULTIKEY(String::from("")), add_root_category);
-    kbm.add("cn", keybind_manager::KeybindMode::MULTIKEY(String::from("")), change_name);
-    kbm.add("cf", keybind_manager::KeybindMode::MULTIKEY(String::from("")), change_file);
-    kbm.add("sn", keybind_manager::KeybindMode::MULTIKEY(String::from("")), |nm, mode| {
+    kbm.register("delete", "Delete the entry, a count deletes the ones after it too", delete_entry);
+    kbm.register("add-note", "Add a note to the category, a count adds that many", add_note);
+    kbm.register("cursor-down", "Move down", cursor_down);
+    kbm.register("cursor-up", "Move up", cursor_up);
+    kbm.register("page-down", "Scroll down a page", page_down);
+    kbm.register("page-up", "Scroll up a page", page_up);
+    kbm.register("half-page-down", "Scroll down half a page", half_page_down);
+    kbm.register("half-page-up", "Scroll up half a page", half_page_up);
+    kbm.register("cursor-top", "Move to the first entry, or to the entry given as a count", cursor_top);
+    kbm.register("cursor-bottom", "Move to the last entry, or to the entry given as a count", cursor_bottom);
+    kbm.register("cursor-parent", "Move to the category the entry is in", cursor_parent);
+    kbm.register("next-sibling", "Move to the next entry in the same category", next_sibling);
+    kbm.register("previous-sibling", "Move to the previous entry in the same category", previous_sibling);
+    kbm.register("scroll-center", "Scroll the entry to the middle of the screen", scroll_center);
+    kbm.register("scroll-top", "Scroll the entry to the top of the screen", scroll_top);
+    kbm.register("scroll-bottom", "Scroll the entry to the bottom of the screen", scroll_bottom);
+    kbm.register("undo", "Undo the last change", undo);
+    kbm.register("redo", "Redo the last undone change", redo);
+    kbm.register("select", "Open the note or open and close the category", select);
+    kbm.register("quit", "Save and quit", close);
+    kbm.register("move-start", "Pick the entry to move, a count picks the ones after it too", move_start);
+    kbm.register("move-complete", "Move the picked entries into the category", move_complete);
+    kbm.register("add-category", "Add a category to the category, a count adds that many", add_category);
+    kbm.register("import-directory", "Import a directory into the category", import_directory);
+    kbm.register("import-outline", "Import an OPML or Markdown outline into the category", import_outline);
+    kbm.register("add-root-category", "Add a top level category, a count adds that many", add_root_category);
+    kbm.register("change-name", "Rename the entry", change_name);
+    kbm.register("change-file", "Change the file of the entry", change_file);
+    kbm.register("find", "Find an entry by name", find_entry);
+    kbm.register("search-contents", "Search the contents of every note", search_contents);
+    kbm.register("open-with", "Open the note with another program", open_with);
+    kbm.register("rebuild-index", "Rebuild the search index", rebuild_index);
+    kbm.register("add-tags", "Add tags to the entry", add_tags);
+    kbm.register("remove-tag", "Remove a tag from the entry", remove_tag);
+    kbm.register("filter-tags", "Only show entries matching tags", filter_tags);
+    kbm.register("sync", "Sync the notebook with the files on disk", sync_files);
+    kbm.register("export", "Export the notebook", export_notebook);
+    kbm.register("restore-snapshot", "Restore a snapshot", restore_snapshot);
+    kbm.register("expand", "Open the category, a count opens that many levels", expand);
+    kbm.register("collapse", "Close the category, a count closes that many levels", collapse);
+    kbm.register("repeat", "Repeat the last change", repeat);
+    kbm.register("sort-name", "Sort the category by name", |nm, mode, _count| {
         sort_category(nm, mode, notes::SortType::NAME) 
     });
-    kbm.add("sf", keybind_manager::KeybindMode::MULTIKEY(String::from("")), |nm, mode| {
+    kbm.register("sort-file", "Sort the category by file", |nm, mode, _count| {
         sort_category(nm, mode, notes::SortType::FILE) 
     });
-    kbm.add("st", keybind_manager::KeybindMode::MULTIKEY(String::from("")), |nm, mode| {
+    kbm.register("sort-time", "Sort the category by time created", |nm, mode, _count| {
         sort_category(nm, mode, notes::SortType::TIME) 
     });
-    kbm.add("sd", keybind_manager::KeybindMode::MULTIKEY(String::from("")), |nm, mode| {
+    kbm.register("sort-descending", "Sort the category descending", |nm, mode, _count| {
         sort_direction(nm, mode, true)  
     });
-    kbm.add("sa", keybind_manager::KeybindMode::MULTIKEY(String::from("")), |nm, mode| {
+    kbm.register("sort-ascending", "Sort the category ascending", |nm, mode, _count| {
         sort_direction(nm, mode, false) 
     });
+
+    bind(kbm, "d", keybind_manager::KeybindMode::DEFAULT, "delete");
+    bind(kbm, "an", keybind_manager::KeybindMode::DEFAULT, "add-note");
+    bind(kbm, "j", keybind_manager::KeybindMode::ALL, "cursor-down");
+    bind(kbm, "k", keybind_manager::KeybindMode::ALL, "cursor-up");
+    bind(kbm, "<Down>", keybind_manager::KeybindMode::ALL, "cursor-down");
+    bind(kbm, "<Up>", keybind_manager::KeybindMode::ALL, "cursor-up");
+    bind(kbm, "<PageDown>", keybind_manager::KeybindMode::ALL, "page-down");
+    bind(kbm, "<PageUp>", keybind_manager::KeybindMode::ALL, "page-up");
+    bind(kbm, "<Home>", keybind_manager::KeybindMode::ALL, "cursor-top");
+    bind(kbm, "<End>", keybind_manager::KeybindMode::ALL, "cursor-bottom");
+    bind(kbm, "<C-f>", keybind_manager::KeybindMode::ALL, "page-down");
+    bind(kbm, "<C-b>", keybind_manager::KeybindMode::ALL, "page-up");
+    bind(kbm, "<C-d>", keybind_manager::KeybindMode::ALL, "half-page-down");
+    bind(kbm, "<C-u>", keybind_manager::KeybindMode::ALL, "half-page-up");
+    bind(kbm, "gg", keybind_manager::KeybindMode::DEFAULT, "cursor-top");
+    bind(kbm, "G", keybind_manager::KeybindMode::ALL, "cursor-bottom");
+    bind(kbm, "gp", keybind_manager::KeybindMode::DEFAULT, "cursor-parent");
+    bind(kbm, "J", keybind_manager::KeybindMode::ALL, "next-sibling");
+    bind(kbm, "K", keybind_manager::KeybindMode::ALL, "previous-sibling");
+    bind(kbm, "zz", keybind_manager::KeybindMode::DEFAULT, "scroll-center");
+    bind(kbm, "zt", keybind_manager::KeybindMode::DEFAULT, "scroll-top");
+    bind(kbm, "zb", keybind_manager::KeybindMode::DEFAULT, "scroll-bottom");
+    bind(kbm, "u", keybind_manager::KeybindMode::DEFAULT, "undo");
+    bind(kbm, "al", keybind_manager::KeybindMode::DEFAULT, "redo");
+    bind(kbm, "<CR>", keybind_manager::KeybindMode::DEFAULT, "select");
+    bind(kbm, "q", keybind_manager::KeybindMode::DEFAULT, "quit");
+    bind(kbm, "m", keybind_manager::KeybindMode::DEFAULT, "move-start");
+    bind(kbm, "<CR>", keybind_manager::KeybindMode::MOVE(Vec::new()), "move-complete");
+    bind(kbm, "ac", keybind_manager::KeybindMode::DEFAULT, "add-category");
+    bind(kbm, "ai", keybind_manager::KeybindMode::DEFAULT, "import-directory");
+    bind(kbm, "ao", keybind_manager::KeybindMode::DEFAULT, "import-outline");
+    bind(kbm, "ar", keybind_manager::KeybindMode::DEFAULT, "add-root-category");
+    bind(kbm, "cn", keybind_manager::KeybindMode::DEFAULT, "change-name");
+    bind(kbm, "cf", keybind_manager::KeybindMode::DEFAULT, "change-file");
+    bind(kbm, "/", keybind_manager::KeybindMode::DEFAULT, "find");
+    bind(kbm, "F", keybind_manager::KeybindMode::DEFAULT, "search-contents");
+    bind(kbm, "ow", keybind_manager::KeybindMode::DEFAULT, "open-with");
+    bind(kbm, "ri", keybind_manager::KeybindMode::DEFAULT, "rebuild-index");
+    bind(kbm, "ta", keybind_manager::KeybindMode::DEFAULT, "add-tags");
+    bind(kbm, "tr", keybind_manager::KeybindMode::DEFAULT, "remove-tag");
+    bind(kbm, "tf", keybind_manager::KeybindMode::DEFAULT, "filter-tags");
+    bind(kbm, "S", keybind_manager::KeybindMode::DEFAULT, "sync");
+    bind(kbm, "E", keybind_manager::KeybindMode::DEFAULT, "export");
+    bind(kbm, "b", keybind_manager::KeybindMode::DEFAULT, "restore-snapshot");
+    bind(kbm, "zo", keybind_manager::KeybindMode::DEFAULT, "expand");
+    bind(kbm, "zc", keybind_manager::KeybindMode::DEFAULT, "collapse");
+    bind(kbm, "<Right>", keybind_manager::KeybindMode::DEFAULT, "expand");
+    bind(kbm, "<Left>", keybind_manager::KeybindMode::DEFAULT, "collapse");
+    bind(kbm, ".", keybind_manager::KeybindMode::DEFAULT, "repeat");
+    bind(kbm, "sn", keybind_manager::KeybindMode::DEFAULT, "sort-name");
+    bind(kbm, "sf", keybind_manager::KeybindMode::DEFAULT, "sort-file");
+    bind(kbm, "st", keybind_manager::KeybindMode::DEFAULT, "sort-time");
+    bind(kbm, "sd", keybind_manager::KeybindMode::DEFAULT, "sort-descending");
+    bind(kbm, "sa", keybind_manager::KeybindMode::DEFAULT, "sort-ascending");
+}
+
+//The default keys only use registered commands, so binding them can't fail
+fn bind(kbm: &mut keybind_manager::KeybindManager, keys: &str, mode: keybind_manager::KeybindMode, command: &str) {
+    let keys = keys::parse_keys(keys).expect("default key with a bad key name");
+    kbm.bind(&keys, mode, command).expect("default key bound to an unknown command");
 }
 
-fn delete_entry(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
-    let delete = DeleteAction::new(nm.root.get_nth_child(nm.cursor).unwrap().get_id());
-    delete.activate(nm);
+fn delete_entry(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let count = count.unwrap_or(1);
 
-    nm.actions.add(Box::new(delete));
+    delete_entries(nm, count);
+    nm.set_repeat(count, delete_entries);
 
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn cursor_up(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
-    nm.move_cursor(-1);
+fn delete_entries(nm: &mut note_manager::NoteManager, count: usize) {
+    let mut delete_actions: Vec<Box<Action>> = Vec::new();
+    for id in get_sibling_ids(nm, count) {
+        delete_actions.push(Box::new(DeleteAction::new(id)));
+    }
+
+    let delete = GroupAction::new(delete_actions);
+    if !delete.is_empty() {
+        delete.activate(nm);
+        nm.actions.add(Box::new(delete));
+    }
+}
+
+//The entry under the cursor and the ones after it in the same category, count entries in all
+fn get_sibling_ids(nm: &note_manager::NoteManager, count: usize) -> Vec<u32> {
+    let id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => entry.get_id(),
+        None => return Vec::new(),
+    };
+
+    match nm.root.get_parent_by_id(id) {
+        Some(parent) => parent.get_visible_children().iter().map(|child| child.get_id()).skip_while(|&child_id| child_id != id).take(count).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn cursor_up(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    nm.move_cursor(-(count.unwrap_or(1) as i32));
+    None
+}
+
+fn cursor_down(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    nm.move_cursor(count.unwrap_or(1) as i32);
+    None
+}
+
+fn page_down(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let page = nm.get_page_height();
+    nm.scroll_by(page * count.unwrap_or(1) as i32);
+    None
+}
+
+fn page_up(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let page = nm.get_page_height();
+    nm.scroll_by(-page * count.unwrap_or(1) as i32);
+    None
+}
+
+fn half_page_down(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let half_page = get_half_page(nm);
+    nm.scroll_by(half_page * count.unwrap_or(1) as i32);
+    None
+}
+
+fn half_page_up(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let half_page = get_half_page(nm);
+    nm.scroll_by(-half_page * count.unwrap_or(1) as i32);
+    None
+}
+
+//Rounded down, but a window with one line still scrolls
+fn get_half_page(nm: &note_manager::NoteManager) -> i32 {
+    (nm.get_page_height() / 2).max(1)
+}
+
+//A count is the number of the entry to go to, counting from 1 like the lines on screen
+fn cursor_top(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    nm.set_cursor(count.unwrap_or(1) as i32 - 1);
     None
 }
 
-fn cursor_down(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
-    nm.move_cursor(1);
+fn cursor_bottom(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    nm.set_cursor(count.map_or(i32::MAX, |count| count as i32 - 1));
     None
 }
 
-fn undo(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+//Entries directly in the root have no parent to go to
+fn cursor_parent(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let mut id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => entry.get_id(),
+        None => return None,
+    };
+
+    for _ in 0..count.unwrap_or(1) {
+        match nm.root.get_parent_by_id(id) {
+            Some(parent) if parent.get_id() != nm.root.get_id() => id = parent.get_id(),
+            _ => break,
+        }
+    }
+
+    nm.set_cursor_to_entry(id);
+    None
+}
+
+fn next_sibling(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    move_to_sibling(nm, count.unwrap_or(1) as i32);
+    None
+}
+
+fn previous_sibling(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    move_to_sibling(nm, -(count.unwrap_or(1) as i32));
+    None
+}
+
+//Skips over the entries inside open categories, stopping at the first or last entry of the category
+fn move_to_sibling(nm: &mut note_manager::NoteManager, offset: i32) {
+    let id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => entry.get_id(),
+        None => return,
+    };
+
+    let sibling_id = match nm.root.get_parent_by_id(id) {
+        Some(parent) => {
+            let siblings = parent.get_visible_children();
+            match siblings.iter().position(|sibling| sibling.get_id() == id) {
+                Some(position) => siblings[(position as i32 + offset).min(siblings.len() as i32 - 1).max(0) as usize].get_id(),
+                None => return,
+            }
+        },
+        None => return,
+    };
+
+    nm.set_cursor_to_entry(sibling_id);
+}
+
+//The view moves so the cursor ends up in the middle, at the top or at the bottom, as far as the list allows
+fn scroll_center(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let scroll = nm.cursor - nm.get_page_height() / 2;
+    nm.set_scroll(scroll);
+    None
+}
+
+fn scroll_top(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let scroll = nm.cursor;
+    nm.set_scroll(scroll);
+    None
+}
+
+fn scroll_bottom(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let scroll = nm.cursor - nm.get_page_height() + 1;
+    nm.set_scroll(scroll);
+    None
+}
+
+fn repeat(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    nm.repeat_last_change(count);
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+//Opens the category under the cursor and the categories inside it, count levels deep
+fn expand(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) if entry.can_add_child() => entry.get_id(),
+        _ => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    nm.root.get_mut_child_by_id(id).unwrap().open_levels(count.unwrap_or(1));
+    nm.render();
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+//Closes the open category the cursor is in, and count - 1 more around it
+fn collapse(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let mut id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) if entry.can_add_child() && entry.is_open() => Some(entry.get_id()),
+        Some(entry) => nm.root.get_parent_by_id(entry.get_id()).map(|parent| parent.get_id()),
+        None => None,
+    };
+
+    let mut closed = None;
+    for _ in 0..count.unwrap_or(1) {
+        match id {
+            Some(category_id) if category_id != nm.root.get_id() => {
+                nm.root.get_mut_child_by_id(category_id).unwrap().set_is_open(false);
+                closed = Some(category_id);
+                id = nm.root.get_parent_by_id(category_id).map(|parent| parent.get_id());
+            },
+            _ => break,
+        }
+    }
+
+    match closed {
+        Some(category_id) => nm.reveal_entry(category_id),
+        None => nm.render(),
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn undo(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
     match nm.actions.get_undo() {
         Some(action) => {
             action.undo(nm);
@@ -65,7 +342,7 @@ fn undo(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn redo(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn redo(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
      match nm.actions.get_redo() {
         Some(action) => {
             action.activate(nm);
@@ -76,7 +353,7 @@ fn redo(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn select(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn select(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
     match nm.selected {
         Some(selected) => {
             let move_action = MoveAction::new(selected, nm.root.get_parent_by_id(selected).unwrap().get_id(), nm.root.get_nth_child(nm.cursor).unwrap().get_id());
@@ -88,32 +365,37 @@ fn select(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMo
         },
         None => {
             let id = nm.root.get_nth_child(nm.cursor).unwrap().get_id();
-            nm.root.get_mut_child_by_id(id).unwrap().activate();
-            nm.render();
+            nm.activate_entry(id);
         }
     }
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn close(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn close(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
     nm.end(); 
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn move_start(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
-    match nm.root.get_nth_child(nm.cursor) {
-        Some(child) => Some(keybind_manager::KeybindMode::MOVE(child.get_id())),
-        None => None,
+//A count picks up that many entries from the cursor down
+fn move_start(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let ids = get_sibling_ids(nm, count.unwrap_or(1));
+
+    match ids.is_empty() {
+        true => None,
+        false => Some(keybind_manager::KeybindMode::MOVE(ids)),
     }
 }
 
-fn move_complete(nm: &mut note_manager::NoteManager, mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn move_complete(nm: &mut note_manager::NoteManager, mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
     match mode {
-        keybind_manager::KeybindMode::MOVE(id) => {
-            let move_action = MoveAction::new(*id, nm.root.get_parent_by_id(*id).unwrap().get_id(), nm.root.get_nth_child(nm.cursor).unwrap().get_id());
-            
-            move_action.activate(nm);
-            nm.actions.add(Box::new(move_action));
+        keybind_manager::KeybindMode::MOVE(ids) => {
+            let category_id = nm.root.get_nth_child(nm.cursor).unwrap().get_id();
+
+            move_entries(nm, ids, category_id);
+            nm.set_repeat(ids.len(), move |nm, count| {
+                let ids = get_sibling_ids(nm, count);
+                move_entries(nm, &ids, category_id);
+            });
         },
         _ => (),
     }
@@ -121,17 +403,51 @@ fn move_complete(nm: &mut note_manager::NoteManager, mode: &keybind_manager::Key
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn add_note(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn move_entries(nm: &mut note_manager::NoteManager, ids: &Vec<u32>, category_id: u32) {
+    if nm.root.get_child_by_id(category_id).is_none() {
+        return;
+    }
+
+    let mut move_actions: Vec<Box<Action>> = Vec::new();
+    let mut skipped = false;
+    for id in ids.iter() {
+        //The target itself or anything containing it can not go inside it
+        if nm.root.get_child_by_id(*id).map_or(false, |entry| entry.get_child_by_id(category_id).is_some()) {
+            skipped = true;
+            continue;
+        }
+
+        if let Some(parent) = nm.root.get_parent_by_id(*id) {
+            move_actions.push(Box::new(MoveAction::new(*id, parent.get_id(), category_id)));
+        }
+    }
+
+    let move_action = GroupAction::new(move_actions);
+    if !move_action.is_empty() {
+        move_action.activate(nm);
+        nm.actions.add(Box::new(move_action));
+    }
+
+    if skipped {
+        nm.set_message("A category can not be moved into itself");
+    }
+}
+
+//A count adds that many copies
+fn add_note(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let count = count.unwrap_or(1);
+
     if nm.root.get_nth_child(nm.cursor).unwrap().can_add_child() {
         match nm.get_text_input("Input new note name") {
             Some(name) => {
                 match nm.get_text_input("Input path to file") {
                     Some(file_location) => {
-                        let new_child = notes::EntryBuilder::new(nm.next_id).set_text(name.as_str()).set_file_location(file_location, nm, nm.root.get_nth_child(nm.cursor).unwrap().get_id()).build();
-                        let add = AddAction::new(nm.root.get_nth_child(nm.cursor).unwrap().get_id(), new_child);
-                        add.activate(nm);
-
-                        nm.actions.add(Box::new(add));
+                        add_entry(nm, count, name.as_str(), file_location.as_str(), false);
+                        nm.set_repeat(count, move |nm, count| {
+                            if nm.root.get_nth_child(nm.cursor).map_or(false, |entry| entry.can_add_child()) {
+                                add_entry(nm, count, name.as_str(), file_location.as_str(), false);
+                            }
+                        });
                     },
                     None => () 
                 }
@@ -143,20 +459,42 @@ fn add_note(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::Keybind
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn add_category(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+//Adds notes or categories to the category under the cursor
+fn add_entry(nm: &mut note_manager::NoteManager, count: usize, name: &str, file_location: &str, is_category: bool) {
+    let category_id = nm.root.get_nth_child(nm.cursor).unwrap().get_id();
+    add_entries(nm, category_id, count, name, file_location, is_category);
+}
+
+//The copies are undone together, so their ids are handed out here instead of by each AddAction
+fn add_entries(nm: &mut note_manager::NoteManager, category_id: u32, count: usize, name: &str, file_location: &str, is_category: bool) {
+    let mut add_actions: Vec<Box<Action>> = Vec::new();
+    for i in 0..count {
+        let new_child = notes::EntryBuilder::new(nm.next_id + i as u32).set_text(name).set_is_category(is_category).set_file_location(String::from(file_location), nm, category_id).build();
+        add_actions.push(Box::new(AddAction::new(category_id, new_child)));
+    }
+
+    let add = GroupAction::new(add_actions);
+    if !add.is_empty() {
+        add.activate(nm);
+        nm.actions.add(Box::new(add));
+    }
+}
+
+//A count adds that many copies
+fn add_category(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let count = count.unwrap_or(1);
+
     if nm.root.get_children().len() > 0 {
         match nm.get_text_input("Input new category name") {
             Some(name) => {
                 match nm.get_text_input("Input path to directory") {
                     Some(file_location) => {
-                        let category_id = nm.root.get_nth_child(nm.cursor).unwrap().get_id();
-
-                        let new_child = notes::EntryBuilder::new(nm.next_id).set_text(name.as_str()).set_is_category(true).set_file_location(file_location, nm, category_id).build();
-
-                        let add = AddAction::new(category_id, new_child);
-                        add.activate(nm);
-
-                        nm.actions.add(Box::new(add));
+                        add_entry(nm, count, name.as_str(), file_location.as_str(), true);
+                        nm.set_repeat(count, move |nm, count| {
+                            if nm.root.get_nth_child(nm.cursor).is_some() {
+                                add_entry(nm, count, name.as_str(), file_location.as_str(), true);
+                            }
+                        });
                     },
                     None => (),
                 }
@@ -170,16 +508,19 @@ fn add_category(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::Key
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn add_root_category(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn add_root_category(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let count = count.unwrap_or(1);
+
     match nm.get_text_input("Input new category name") {
         Some(name) => {
             match nm.get_text_input("Input path to directory") {
                 Some(file_location) => {
-                    let new_child = notes::EntryBuilder::new(nm.next_id).set_text(name.as_str()).set_is_category(true).set_file_location(file_location, nm, nm.root.get_id()).build();
-                    let add = AddAction::new(nm.root.get_id(), new_child);
-                    add.activate(nm);
-
-                    nm.actions.add(Box::new(add));
+                    let root_id = nm.root.get_id();
+                    add_entries(nm, root_id, count, name.as_str(), file_location.as_str(), true);
+                    nm.set_repeat(count, move |nm, count| {
+                        let root_id = nm.root.get_id();
+                        add_entries(nm, root_id, count, name.as_str(), file_location.as_str(), true);
+                    });
                 },
                 None => (),
             }
@@ -190,13 +531,11 @@ fn add_root_category(nm: &mut note_manager::NoteManager, _mode: &keybind_manager
     Some(keybind_manager::KeybindMode::DEFAULT)
 } 
 
-fn change_name(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn change_name(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
     match nm.get_text_input("Input new name") {
         Some (name) => {
-            let rename = RenameAction::new(nm.root.get_nth_child(nm.cursor).unwrap(), name.as_str());
-            rename.activate(nm);
-
-            nm.actions.add(Box::new(rename));
+            rename_entry(nm, name.as_str());
+            nm.set_repeat(1, move |nm, _| rename_entry(nm, name.as_str()));
         },
         None => ()
     }
@@ -204,13 +543,21 @@ fn change_name(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::Keyb
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn change_file (nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode) -> Option<keybind_manager::KeybindMode> {
+fn rename_entry(nm: &mut note_manager::NoteManager, name: &str) {
+    let rename = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => RenameAction::new(entry, name),
+        None => return,
+    };
+    rename.activate(nm);
+
+    nm.actions.add(Box::new(rename));
+}
+
+fn change_file (nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
     match nm.get_text_input("Input new file location") {
         Some(name) => {
-            let change_file = ChangeFileAction::new(nm.root.get_nth_child(nm.cursor).unwrap(), name.as_str());
-            change_file.activate(nm);
-
-            nm.actions.add(Box::new(change_file));
+            change_entry_file(nm, name.as_str());
+            nm.set_repeat(1, move |nm, _| change_entry_file(nm, name.as_str()));
         },
         None => ()
     }   
@@ -218,20 +565,372 @@ fn change_file (nm: &mut note_manager::NoteManager, _mode: &keybind_manager::Key
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
+fn change_entry_file(nm: &mut note_manager::NoteManager, file_location: &str) {
+    let change_file = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => ChangeFileAction::new(entry, file_location),
+        None => return,
+    };
+    change_file.activate(nm);
+
+    nm.actions.add(Box::new(change_file));
+}
+
 fn sort_category (nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, sort_type: notes::SortType) -> Option<keybind_manager::KeybindMode> {
-    let sort_action = SortAction::new(nm.root.get_nth_child(nm.cursor).unwrap(), sort_type, nm.root.get_nth_child(nm.cursor).unwrap().get_sort_type());
+    sort_entry(nm, sort_type.clone());
+    nm.set_repeat(1, move |nm, _| sort_entry(nm, sort_type.clone()));
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn sort_entry(nm: &mut note_manager::NoteManager, sort_type: notes::SortType) {
+    let sort_action = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => SortAction::new(entry, sort_type, entry.get_sort_type()),
+        None => return,
+    };
     sort_action.activate(nm);
 
     nm.actions.add(Box::new(sort_action));
+}
+
+fn sort_direction (nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, sort_descending: bool) -> Option<keybind_manager::KeybindMode> {
+    set_sort_direction(nm, sort_descending);
+    nm.set_repeat(1, move |nm, _| set_sort_direction(nm, sort_descending));
 
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
 
-fn sort_direction (nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, sort_descending: bool) -> Option<keybind_manager::KeybindMode> {
-    let sort_direction_action = SortDirectionAction::new(nm.root.get_nth_child(nm.cursor).unwrap(), sort_descending, nm.root.get_nth_child(nm.cursor).unwrap().get_sort_descending());
+fn set_sort_direction(nm: &mut note_manager::NoteManager, sort_descending: bool) {
+    let sort_direction_action = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => SortDirectionAction::new(entry, sort_descending, entry.get_sort_descending()),
+        None => return,
+    };
     sort_direction_action.activate(nm);
 
     nm.actions.add(Box::new(sort_direction_action));
+}
+
+fn restore_snapshot(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let snapshots = nm.get_snapshots();
+
+    if snapshots.is_empty() {
+        nm.display_message("No snapshots found");
+    } else {
+        let labels = snapshots.iter().map(|snapshot| backups::snapshot_label(snapshot)).collect();
+
+        match nm.select_from_list("Snapshots", &labels) {
+            Some(i) => {
+                if let Err(e) = nm.restore_snapshot(snapshots[i].as_str()) {
+                    nm.set_message(e.as_str());
+                }
+            },
+            None => (),
+        }
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn import_directory(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let category_id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) if entry.can_add_child() => entry.get_id(),
+        Some(_) => {
+            nm.display_message("Entries can only be imported into a category");
+            return Some(keybind_manager::KeybindMode::DEFAULT);
+        },
+        None => nm.root.get_id(),
+    };
+
+    let directory = match nm.get_text_input("Input directory to import") {
+        Some(directory) => notes::build_full_file_path(directory, nm, category_id, true),
+        None => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    let mut options = import::ImportOptions::new();
+    let mut errors = Vec::new();
+
+    if let Some(patterns) = nm.get_text_input("Include patterns (empty for everything)") {
+        for pattern in patterns.split_whitespace() {
+            if let Err(e) = options.add_include(pattern) {
+                errors.push(e);
+            }
+        }
+    }
+
+    if let Some(patterns) = nm.get_text_input("Exclude patterns") {
+        for pattern in patterns.split_whitespace() {
+            if let Err(e) = options.add_exclude(pattern) {
+                errors.push(e);
+            }
+        }
+    }
+
+    if let Some(max_depth) = nm.get_text_input("Max depth (empty for no limit)") {
+        match max_depth.trim().parse::<usize>() {
+            Ok(max_depth) => options.max_depth = Some(max_depth),
+            Err(_) => errors.push(format!("invalid max depth {}", max_depth)),
+        }
+    }
+
+    if !errors.is_empty() {
+        nm.display_message(errors.join(", ").as_str());
+        return Some(keybind_manager::KeybindMode::DEFAULT);
+    }
+
+    let mut next_id = nm.next_id;
+    match import::import_directory(directory.as_str(), &options, &mut next_id) {
+        Ok(entry) => {
+            let title = format!("Import {} entries? Enter to import, q to cancel", import::count_entries(&entry));
+
+            if nm.select_from_list(title.as_str(), &import::preview(&entry)).is_some() {
+                let add = AddAction::new(category_id, entry);
+                add.activate(nm);
+
+                nm.actions.add(Box::new(add));
+            }
+        },
+        Err(e) => nm.display_message(format!("Could not import {}: {}", directory, e).as_str()),
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn import_outline(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let category_id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) if entry.can_add_child() => entry.get_id(),
+        Some(_) => {
+            nm.display_message("Entries can only be imported into a category");
+            return Some(keybind_manager::KeybindMode::DEFAULT);
+        },
+        None => nm.root.get_id(),
+    };
+
+    let file = match nm.get_text_input("Input OPML or Markdown file to import") {
+        Some(file) => file,
+        None => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    let items = match fs::read_to_string(file.as_str()) {
+        Ok(contents) => match outline::parse(contents.as_str(), outline::format_for_file(file.as_str())) {
+            Ok(items) => items,
+            Err(e) => {
+                nm.display_message(format!("Could not parse {}: {}", file, e).as_str());
+                return Some(keybind_manager::KeybindMode::DEFAULT);
+            },
+        },
+        Err(e) => {
+            nm.display_message(format!("Could not read {}: {}", file, e).as_str());
+            return Some(keybind_manager::KeybindMode::DEFAULT);
+        },
+    };
+
+    if items.is_empty() {
+        nm.display_message(format!("No outline entries found in {}", file).as_str());
+        return Some(keybind_manager::KeybindMode::DEFAULT);
+    }
+
+    let title = format!("Import {} entries? Enter to import, q to cancel", outline::count_items(&items));
+    if nm.select_from_list(title.as_str(), &outline::preview(&items)).is_none() {
+        return Some(keybind_manager::KeybindMode::DEFAULT);
+    }
+
+    let location = outline::category_location(nm.root.get_child_by_id(category_id).unwrap());
+    let mut next_id = nm.next_id;
+    let mut add_actions: Vec<Box<Action>> = Vec::new();
+
+    for entry in outline::build_entries(&items, location.as_str(), &mut next_id) {
+        add_actions.push(Box::new(AddAction::new(category_id, entry)));
+    }
+
+    let import = GroupAction::new(add_actions);
+    import.activate(nm);
+    nm.actions.add(Box::new(import));
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn find_entry(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    if let Some(id) = nm.fuzzy_find() {
+        nm.reveal_entry(id);
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn search_contents(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let query = match nm.get_text_input("Search note contents (/regex/ for a regex, i or c after it to ignore or match case)") {
+        Some(input) => match search::Query::parse(input.as_str()) {
+            Ok(query) => query,
+            Err(e) => {
+                nm.display_message(format!("Invalid search: {}", e).as_str());
+                return Some(keybind_manager::KeybindMode::DEFAULT);
+            },
+        },
+        None => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    let results = nm.search_contents(&query);
+    if results.is_empty() {
+        nm.display_message("No matches found");
+        return Some(keybind_manager::KeybindMode::DEFAULT);
+    }
+
+    let lines = results.iter().map(|result| format!("{}:{}: {}", notes::get_file_name(&result.file), result.line, result.snippet)).collect();
+    let title = format!("{} matches, enter to open", results.len());
+
+    if let Some(i) = nm.select_from_list(title.as_str(), &lines) {
+        nm.reveal_entry(results[i].id);
+        nm.open_entry(results[i].id, Some(results[i].line));
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn open_with(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let id = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) if !entry.can_add_child() && entry.file_location.as_str() != "" => entry.get_id(),
+        _ => {
+            nm.display_message("Only notes with a file can be opened");
+            return Some(keybind_manager::KeybindMode::DEFAULT);
+        },
+    };
+
+    if let Some(command) = nm.get_text_input("Open with ({path} and {line} are filled in, the path is added at the end otherwise)") {
+        nm.open_entry_with(id, command.as_str());
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn rebuild_index(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    match nm.rebuild_index() {
+        Ok(num_files) => nm.display_message(format!("Indexed {} notes", num_files).as_str()),
+        Err(e) => nm.display_message(format!("Could not save the search index: {}", e).as_str()),
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn add_tags(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    if nm.root.get_nth_child(nm.cursor).is_none() {
+        return Some(keybind_manager::KeybindMode::DEFAULT);
+    }
+
+    let input = match nm.get_text_input("Input tags to add") {
+        Some(input) => input,
+        None => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    let mut new_tags = Vec::new();
+    for tag in input.split_whitespace() {
+        match tags::normalize_tag(tag) {
+            Ok(tag) => new_tags.push(tag),
+            Err(e) => {
+                nm.display_message(e.as_str());
+                return Some(keybind_manager::KeybindMode::DEFAULT);
+            },
+        }
+    }
+
+    add_entry_tags(nm, &new_tags);
+    nm.set_repeat(1, move |nm, _| add_entry_tags(nm, &new_tags));
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn add_entry_tags(nm: &mut note_manager::NoteManager, new_tags: &Vec<String>) {
+    let mut add_actions: Vec<Box<Action>> = Vec::new();
+    {
+        let entry = match nm.root.get_nth_child(nm.cursor) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        for tag in new_tags.iter() {
+            if !entry.get_tags().contains(tag) {
+                add_actions.push(Box::new(AddTagAction::new(entry, tag.as_str())));
+            }
+        }
+    }
+
+    let add_tags = GroupAction::new(add_actions);
+    if !add_tags.is_empty() {
+        add_tags.activate(nm);
+        nm.actions.add(Box::new(add_tags));
+    }
+}
+
+fn remove_tag(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let entry_tags: Vec<String> = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) => entry.get_tags().iter().cloned().collect(),
+        None => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    let tag = match entry_tags.len() {
+        0 => {
+            nm.display_message("This entry has no tags");
+            return Some(keybind_manager::KeybindMode::DEFAULT);
+        },
+        1 => entry_tags[0].clone(),
+        _ => match nm.select_from_list("Select a tag to remove, q to cancel", &entry_tags) {
+            Some(i) => entry_tags[i].clone(),
+            None => return Some(keybind_manager::KeybindMode::DEFAULT),
+        },
+    };
+
+    remove_entry_tag(nm, tag.as_str());
+    nm.set_repeat(1, move |nm, _| remove_entry_tag(nm, tag.as_str()));
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn remove_entry_tag(nm: &mut note_manager::NoteManager, tag: &str) {
+    let remove = match nm.root.get_nth_child(nm.cursor) {
+        Some(entry) if entry.get_tags().contains(tag) => RemoveTagAction::new(entry, tag),
+        _ => return,
+    };
+    remove.activate(nm);
+
+    nm.actions.add(Box::new(remove));
+}
+
+fn filter_tags(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    match nm.get_text_input("Filter by tags, e.g. work AND (meetings OR NOT done) (empty to clear)") {
+        Some(input) => match tags::TagFilter::parse(input.as_str()) {
+            Ok(filter) => nm.set_filter(Some(filter)),
+            Err(e) => nm.display_message(format!("Invalid filter: {}", e).as_str()),
+        },
+        None => nm.set_filter(None),
+    }
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn sync_files(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    nm.sync_with_files();
+
+    Some(keybind_manager::KeybindMode::DEFAULT)
+}
+
+fn export_notebook(nm: &mut note_manager::NoteManager, _mode: &keybind_manager::KeybindMode, _count: Option<usize>) -> Option<keybind_manager::KeybindMode> {
+    let format = match nm.get_text_input("Export format (markdown, html or opml)") {
+        Some(format) => match export::get_format_from_str(format.trim()) {
+            Ok(format) => format,
+            Err(e) => {
+                nm.display_message(e.as_str());
+                return Some(keybind_manager::KeybindMode::DEFAULT);
+            },
+        },
+        None => return Some(keybind_manager::KeybindMode::DEFAULT),
+    };
+
+    if let Some(output) = nm.get_text_input("Input path to export to") {
+        let inline_notes = format == export::ExportFormat::HTML && nm.get_bool_input("Include note contents in the pages?", false);
+
+        match export::export(&nm.root, nm.get_title(), format, output.as_str(), inline_notes) {
+            Ok(_) => nm.display_message(format!("Exported to {}", output).as_str()),
+            Err(e) => nm.display_message(format!("Could not export to {}: {}", output, e).as_str()),
+        }
+    }
 
     Some(keybind_manager::KeybindMode::DEFAULT)
 }
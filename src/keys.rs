@@ -0,0 +1,364 @@
+use pancurses::Input;
+use std::result::Result;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyCode {
+    CHAR(char),
+    ENTER,
+    ESCAPE,
+    TAB,
+    BACKSPACE,
+    DELETE,
+    INSERT,
+    UP,
+    DOWN,
+    LEFT,
+    RIGHT,
+    HOME,
+    END,
+    PAGEUP,
+    PAGEDOWN,
+    F(u8),
+    RESIZE,
+    MOUSE,
+    //Every other curses key, named like its Input variant without the Key in front
+    OTHER(String),
+}
+
+//A key press, Alt can go with any key, Ctrl with the characters that have a control character and Shift with the keys that don't have an upper case one
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+//The first name for a key is the one used when showing it
+const KEY_NAMES: [(&str, KeyCode); 23] = [
+    ("CR", KeyCode::ENTER),
+    ("Enter", KeyCode::ENTER),
+    ("Return", KeyCode::ENTER),
+    ("Esc", KeyCode::ESCAPE),
+    ("Tab", KeyCode::TAB),
+    ("BS", KeyCode::BACKSPACE),
+    ("Backspace", KeyCode::BACKSPACE),
+    ("Del", KeyCode::DELETE),
+    ("Delete", KeyCode::DELETE),
+    ("Insert", KeyCode::INSERT),
+    ("Ins", KeyCode::INSERT),
+    ("Up", KeyCode::UP),
+    ("Down", KeyCode::DOWN),
+    ("Left", KeyCode::LEFT),
+    ("Right", KeyCode::RIGHT),
+    ("Home", KeyCode::HOME),
+    ("End", KeyCode::END),
+    ("PageUp", KeyCode::PAGEUP),
+    ("PgUp", KeyCode::PAGEUP),
+    ("PageDown", KeyCode::PAGEDOWN),
+    ("PgDn", KeyCode::PAGEDOWN),
+    ("Resize", KeyCode::RESIZE),
+    ("Mouse", KeyCode::MOUSE),
+];
+
+//Characters that can't be written as themselves inside <>
+const CHAR_NAMES: [(&str, char); 5] = [
+    ("Space", ' '),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("Bslash", '\\'),
+    ("Bar", '|'),
+];
+
+const OTHER_KEYS: [&str; 70] = [
+    "CodeYes", "Break", "DL", "IL", "EIC", "Clear", "EOS", "EOL", "STab", "CTab", "CATab", "SReset",
+    "Reset", "Print", "LL", "Abort", "SHelp", "LHelp", "Beg", "Cancel", "Close", "Command", "Copy", "Create",
+    "Exit", "Find", "Help", "Mark", "Message", "Move", "Next", "Open", "Options", "Previous", "Redo", "Reference",
+    "Refresh", "Replace", "Restart", "Resume", "Save", "SBeg", "SCancel", "SCommand", "SCopy", "SCreate", "SDL", "Select",
+    "SEOL", "SExit", "SFind", "SMessage", "SMove", "SOptions", "SPrint", "SRedo", "SReplace", "SResume", "SSave", "SSuspend",
+    "SUndo", "Suspend", "Undo", "Event", "A1", "A3", "B2", "C1", "C3", "F0",
+];
+
+impl Key {
+    pub fn new(code: KeyCode) -> Key {
+        Key {
+            code: code,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    //Control characters become Ctrl with the key that was held, so <C-d> and the \x04 a terminal sends are the same key
+    pub fn from_char(c: char) -> Key {
+        match c {
+            '\n' | '\r' => Key::new(KeyCode::ENTER),
+            '\t' => Key::new(KeyCode::TAB),
+            '\u{1b}' => Key::new(KeyCode::ESCAPE),
+            '\u{7f}' | '\u{8}' => Key::new(KeyCode::BACKSPACE),
+            '\u{0}' => Key::new(KeyCode::CHAR(' ')).set_ctrl(),
+            '\u{1}'..='\u{1f}' => Key::new(KeyCode::CHAR((((c as u8) | 0x40) as char).to_ascii_lowercase())).set_ctrl(),
+            _ => Key::new(KeyCode::CHAR(c)),
+        }
+    }
+
+    pub fn from_input(input: Input) -> Key {
+        match input {
+            Input::Character(c) => Key::from_char(c),
+            Input::KeyEnter => Key::new(KeyCode::ENTER),
+            Input::KeyBackspace => Key::new(KeyCode::BACKSPACE),
+            Input::KeyDC => Key::new(KeyCode::DELETE),
+            Input::KeyIC => Key::new(KeyCode::INSERT),
+            Input::KeyUp => Key::new(KeyCode::UP),
+            Input::KeyDown => Key::new(KeyCode::DOWN),
+            Input::KeyLeft => Key::new(KeyCode::LEFT),
+            Input::KeyRight => Key::new(KeyCode::RIGHT),
+            Input::KeyHome => Key::new(KeyCode::HOME),
+            Input::KeyEnd => Key::new(KeyCode::END),
+            Input::KeyPPage => Key::new(KeyCode::PAGEUP),
+            Input::KeyNPage => Key::new(KeyCode::PAGEDOWN),
+            Input::KeyBTab => Key::new(KeyCode::TAB).set_shift(),
+            Input::KeySDC => Key::new(KeyCode::DELETE).set_shift(),
+            Input::KeySIC => Key::new(KeyCode::INSERT).set_shift(),
+            Input::KeySR => Key::new(KeyCode::UP).set_shift(),
+            Input::KeySF => Key::new(KeyCode::DOWN).set_shift(),
+            Input::KeySLeft => Key::new(KeyCode::LEFT).set_shift(),
+            Input::KeySRight => Key::new(KeyCode::RIGHT).set_shift(),
+            Input::KeySHome => Key::new(KeyCode::HOME).set_shift(),
+            Input::KeySEnd => Key::new(KeyCode::END).set_shift(),
+            Input::KeySPrevious => Key::new(KeyCode::PAGEUP).set_shift(),
+            Input::KeySNext => Key::new(KeyCode::PAGEDOWN).set_shift(),
+            Input::KeyF1 => Key::new(KeyCode::F(1)),
+            Input::KeyF2 => Key::new(KeyCode::F(2)),
+            Input::KeyF3 => Key::new(KeyCode::F(3)),
+            Input::KeyF4 => Key::new(KeyCode::F(4)),
+            Input::KeyF5 => Key::new(KeyCode::F(5)),
+            Input::KeyF6 => Key::new(KeyCode::F(6)),
+            Input::KeyF7 => Key::new(KeyCode::F(7)),
+            Input::KeyF8 => Key::new(KeyCode::F(8)),
+            Input::KeyF9 => Key::new(KeyCode::F(9)),
+            Input::KeyF10 => Key::new(KeyCode::F(10)),
+            Input::KeyF11 => Key::new(KeyCode::F(11)),
+            Input::KeyF12 => Key::new(KeyCode::F(12)),
+            Input::KeyF13 => Key::new(KeyCode::F(13)),
+            Input::KeyF14 => Key::new(KeyCode::F(14)),
+            Input::KeyF15 => Key::new(KeyCode::F(15)),
+            Input::KeyResize => Key::new(KeyCode::RESIZE),
+            Input::KeyMouse => Key::new(KeyCode::MOUSE),
+            Input::Unknown(code) => Key::new(KeyCode::OTHER(format!("Unknown{}", code))),
+            _ => Key::new(KeyCode::OTHER(format!("{:?}", input).trim_start_matches("Key").to_string())),
+        }
+    }
+
+    pub fn set_ctrl(mut self) -> Key {
+        self.ctrl = true;
+        self
+    }
+
+    pub fn set_alt(mut self) -> Key {
+        self.alt = true;
+        self
+    }
+
+    pub fn set_shift(mut self) -> Key {
+        self.shift = true;
+        self
+    }
+
+    //The character typed, if it is a plain one without Ctrl or Alt
+    pub fn get_char(&self) -> Option<char> {
+        match self.code {
+            KeyCode::CHAR(c) if !self.ctrl && !self.alt => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn is(&self, code: KeyCode) -> bool {
+        self.code == code && !self.ctrl && !self.alt && !self.shift
+    }
+}
+
+//Reads keys written like dd, <C-d>, <A-x>, <S-Tab>, <CR> or <PageDown>, a < that doesn't start a key name is just <
+pub fn parse_keys(notation: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+
+    while let Some(c) = rest.chars().next() {
+        let name_end = match c {
+            '<' => rest.find('>').filter(|&end| end > 1),
+            _ => None,
+        };
+
+        match name_end {
+            Some(end) => {
+                keys.push(parse_key_name(&rest[1..end])?);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                keys.push(Key::from_char(c));
+                rest = &rest[c.len_utf8()..];
+            },
+        }
+    }
+
+    if keys.is_empty() {
+        return Err(String::from("no keys given"));
+    }
+
+    Ok(keys)
+}
+
+fn parse_key_name(notation: &str) -> Result<Key, String> {
+    let mut name = notation;
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
+
+    //Modifiers come first, the last part is the key even if it is a - on its own
+    while name.len() > 2 && name.as_bytes()[1] == b'-' {
+        match name.as_bytes()[0].to_ascii_uppercase() {
+            b'C' => ctrl = true,
+            b'A' | b'M' => alt = true,
+            b'S' => shift = true,
+            _ => return Err(format!("unknown modifier in <{}>, use C-, A- or S-", notation)),
+        }
+
+        name = &name[2..];
+    }
+
+    let mut key = if name.chars().count() == 1 {
+        Key::new(KeyCode::CHAR(name.chars().next().unwrap()))
+    } else if let Some(&(_, ref code)) = KEY_NAMES.iter().find(|&&(key_name, _)| key_name.eq_ignore_ascii_case(name)) {
+        Key::new(code.clone())
+    } else if let Some(&(_, c)) = CHAR_NAMES.iter().find(|&&(char_name, _)| char_name.eq_ignore_ascii_case(name)) {
+        Key::new(KeyCode::CHAR(c))
+    } else if let Some(n) = parse_function_key(name) {
+        Key::new(KeyCode::F(n))
+    } else if let Some(other) = OTHER_KEYS.iter().find(|other| other.eq_ignore_ascii_case(name)) {
+        Key::new(KeyCode::OTHER(other.to_string()))
+    } else if name.starts_with("Unknown") && name.len() > 7 && name[7..].chars().all(|c| c.is_ascii_digit()) {
+        Key::new(KeyCode::OTHER(String::from(name)))
+    } else {
+        return Err(format!("unknown key <{}>", notation));
+    };
+
+    //A terminal sends Ctrl with a character as a control character, so the key has to have one
+    if ctrl {
+        key = match key.code {
+            KeyCode::CHAR(c) if c.is_ascii_alphabetic() || "@[\\]^_ ".contains(c) => match c {
+                ' ' | '@' => Key::from_char('\u{0}'),
+                _ => Key::from_char(((c.to_ascii_uppercase() as u8) & 0x1f) as char),
+            },
+            _ => return Err(format!("<{}> can't be typed, Ctrl only goes with letters and @[\\]^_", notation)),
+        };
+    }
+
+    //Shift with a character is just the upper case character
+    if shift {
+        key = match key.code {
+            KeyCode::CHAR(c) if !key.ctrl => Key::new(KeyCode::CHAR(c.to_uppercase().next().unwrap_or(c))),
+            _ => key.set_shift(),
+        };
+    }
+
+    if alt {
+        key = key.set_alt();
+    }
+
+    Ok(key)
+}
+
+fn parse_function_key(name: &str) -> Option<u8> {
+    if name.len() < 2 || !(name.starts_with("F") || name.starts_with("f")) {
+        return None;
+    }
+
+    name[1..].parse::<u8>().ok().filter(|&n| n >= 1 && n <= 15)
+}
+
+//The notation parse_keys reads, used when showing keys
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(format_key).collect()
+}
+
+pub fn format_key(key: &Key) -> String {
+    let name = match key.code {
+        KeyCode::CHAR(c) if !key.ctrl && !key.alt && c != ' ' && c != '<' => return c.to_string(),
+        KeyCode::CHAR(c) => CHAR_NAMES.iter().find(|&&(_, char_name)| char_name == c).map_or(c.to_string(), |&(char_name, _)| String::from(char_name)),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::OTHER(ref other) => other.clone(),
+        ref code => KEY_NAMES.iter().find(|&&(_, ref key_code)| key_code == code).map_or(String::new(), |&(key_name, _)| String::from(key_name)),
+    };
+
+    let mut modifiers = String::new();
+    if key.ctrl {
+        modifiers.push_str("C-");
+    }
+    if key.alt {
+        modifiers.push_str("A-");
+    }
+    if key.shift {
+        modifiers.push_str("S-");
+    }
+
+    format!("<{}{}>", modifiers, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(notation: &str) -> Vec<Key> {
+        parse_keys(notation).unwrap()
+    }
+
+    #[test]
+    fn ctrl_keys_match_what_the_terminal_sends() {
+        assert_eq!(parse("<C-j>"), parse("<CR>"));
+        assert_eq!(parse("<C-m>"), parse("<Enter>"));
+        assert_eq!(parse("<C-i>"), parse("<Tab>"));
+        assert_eq!(parse("<C-[>"), parse("<Esc>"));
+        assert_eq!(parse("<C-d>"), vec![Key::from_char('\u{4}')]);
+        assert_eq!(parse("<C-D>"), parse("<c-d>"));
+        assert_eq!(parse("<C-Space>"), parse("<C-@>"));
+    }
+
+    #[test]
+    fn angle_brackets() {
+        assert_eq!(parse("<lt>"), vec![Key::from_char('<')]);
+        assert_eq!(parse("<gt>"), vec![Key::from_char('>')]);
+        assert_eq!(parse("<"), vec![Key::from_char('<')]);
+        assert_eq!(parse("<>"), vec![Key::from_char('<'), Key::from_char('>')]);
+        assert_eq!(parse("<A-gt>"), vec![Key::from_char('>').set_alt()]);
+        assert_eq!(parse("a<lt>b"), parse("a<b"));
+    }
+
+    #[test]
+    fn sequences_and_modifiers() {
+        assert_eq!(parse("gg"), vec![Key::from_char('g'), Key::from_char('g')]);
+        assert_eq!(parse("<S-a>"), parse("A"));
+        assert_eq!(parse("<A-x>"), vec![Key::from_char('x').set_alt()]);
+        assert_eq!(parse("<M-x>"), parse("<A-x>"));
+        assert_eq!(parse("<S-Tab>"), vec![Key::new(KeyCode::TAB).set_shift()]);
+        assert_eq!(parse("<PageDown>"), parse("<PgDn>"));
+        assert_eq!(parse("<F12>"), vec![Key::new(KeyCode::F(12))]);
+        assert_eq!(parse("<->"), vec![Key::from_char('-')]);
+        assert_eq!(parse("<A-->"), vec![Key::from_char('-').set_alt()]);
+    }
+
+    #[test]
+    fn bad_notation_is_an_error() {
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("<C-Up>").is_err());
+        assert!(parse_keys("<C-1>").is_err());
+        assert!(parse_keys("<X-a>").is_err());
+        assert!(parse_keys("<Nope>").is_err());
+        assert!(parse_keys("<F16>").is_err());
+    }
+
+    #[test]
+    fn formatting_reads_back() {
+        for notation in ["dd", "<C-d>", "<A-x>", "<S-Tab>", "<CR>", "<lt>", "<Space>", "<F5>", "<C-@>"].iter() {
+            let keys = parse(notation);
+            assert_eq!(parse(format_keys(&keys).as_str()), keys, "{}", notation);
+        }
+    }
+}
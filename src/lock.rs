@@ -0,0 +1,75 @@
+extern crate libc;
+
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::process;
+
+pub struct NotebookLock {
+    path: String,
+}
+
+pub enum LockError {
+    Held(u32),
+    Io(io::Error),
+}
+
+impl NotebookLock {
+    pub fn acquire(file_name: &str) -> Result<NotebookLock, LockError> {
+        let path = format!("{}.lock", file_name);
+
+        //A second attempt is made after clearing a lock left behind by a process that no longer exists
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(path.as_str()) {
+                Ok(mut file) => {
+                    return match write!(file, "{}", process::id()) {
+                        Ok(_) => Ok(NotebookLock { path: path }),
+                        Err(e) => Err(LockError::Io(e)),
+                    };
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    match read_lock_pid(path.as_str()) {
+                        Some(pid) if process_exists(pid) => return Err(LockError::Held(pid)),
+                        _ => {
+                            if let Err(e) = fs::remove_file(path.as_str()) {
+                                return Err(LockError::Io(e));
+                            }
+                        },
+                    }
+                },
+                Err(e) => return Err(LockError::Io(e)),
+            }
+        }
+
+        Err(LockError::Io(io::Error::new(io::ErrorKind::Other, format!("could not create {}", path))))
+    }
+}
+
+impl Drop for NotebookLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.path.as_str());
+    }
+}
+
+fn read_lock_pid(path: &str) -> Option<u32> {
+    let mut contents = String::new();
+
+    match File::open(path) {
+        Ok(mut file) => match file.read_to_string(&mut contents) {
+            Ok(_) => contents.trim().parse::<u32>().ok(),
+            Err(_) => None,
+        },
+        Err(_) => None,
+    }
+}
+
+fn process_exists(pid: u32) -> bool {
+    if pid == process::id() {
+        return false;
+    }
+
+    unsafe {
+        libc::kill(pid as libc::pid_t, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+}
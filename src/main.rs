@@ -1,29 +1,176 @@
 extern crate pancurses;
 extern crate chrono;
+extern crate libc;
 mod notes;
 mod note_manager;
 mod actions;
 mod keybind_manager;
+mod keys;
 mod keybindings;
+mod backups;
+mod schema;
+mod signals;
+mod lock;
+mod store;
+mod import;
+mod cli;
+mod sync;
+mod export;
+mod outline;
+mod tags;
+mod finder;
+mod search;
+mod index;
+mod opener;
+mod config;
 
 use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
 use keybindings::*;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() <= 1 {
-        println!("You need to input a file name");
-        return 
+    if args.len() > 1 && cli::is_command(args[1].as_str()) {
+        process::exit(cli::run(&args[1..]));
     }
 
-    let file = args[1].clone();
+    let mut file = None;
+    let mut restore = None;
+    let mut backup_dir = None;
+    let mut backup_count = None;
+    let mut autosave_interval = None;
+    let mut autosave_actions = None;
+    let mut store_kind = None;
+    let mut sync_at_startup = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--restore" | "--backup-dir" | "--backups" | "--autosave" | "--autosave-actions" => {
+                if i + 1 >= args.len() {
+                    println!("{} needs a value", args[i]);
+                    return
+                }
+
+                match args[i].as_str() {
+                    "--restore" => restore = Some(args[i + 1].clone()),
+                    "--backup-dir" => backup_dir = Some(args[i + 1].clone()),
+                    _ => match args[i + 1].parse::<u64>() {
+                        Ok(n) => match args[i].as_str() {
+                            "--backups" => backup_count = Some(n as usize),
+                            "--autosave" => autosave_interval = Some(n),
+                            _ => autosave_actions = Some(n),
+                        },
+                        Err(_) => {
+                            println!("{} needs a number", args[i]);
+                            return
+                        }
+                    },
+                }
+
+                i += 1;
+            },
+            "--json" => store_kind = Some(store::StoreKind::JSON),
+            "--sqlite" => store_kind = Some(store::StoreKind::SQLITE),
+            "--sync" => sync_at_startup = true,
+            _ => file = Some(args[i].clone()),
+        }
+
+        i += 1;
+    }
+
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    //Without a file argument the notebook from the config is used, then the one in $XDG_DATA_HOME
+    let file = match file.or(config.get_notebook()) {
+        Some(file) => file,
+        None => {
+            println!("You need to input a file name");
+            println!("Usage: {} [file] [--json | --sqlite] [--sync] [--backups <count>] [--backup-dir <dir>] [--restore <snapshot>] [--autosave <seconds>] [--autosave-actions <count>]", args[0]);
+            println!("{}", cli::usage(args[0].as_str()));
+            return 
+        }
+    };
+
+    //Only the default notebook lives in a directory we own, any other path is used as given
+    let default_notebook = config::default_notebook();
+    if let Some(directory) = default_notebook.as_ref().filter(|notebook| Path::new(file.as_str()) == notebook.as_path()).and_then(|notebook| notebook.parent()) {
+        if let Err(e) = fs::create_dir_all(directory) {
+            eprintln!("Could not create {}: {}", directory.display(), e);
+            process::exit(1);
+        }
+    }
+
+    let settings = config.settings_for(file.as_str());
+
+    let store_kind = match store_kind {
+        Some(store_kind) => store_kind,
+        None => store::kind_for_file(file.as_str()),
+    };
+
+    let mut note_manager = note_manager::NoteManager::new(String::from("Notes"), file, store_kind);
+
+    settings.apply(&mut note_manager);
+
+    //Command line options win over the config
+    if let Some(backup_dir) = backup_dir {
+        note_manager.set_backup_dir(backup_dir.as_str());
+    }
+
+    if let Some(backup_count) = backup_count {
+        note_manager.set_backup_count(backup_count);
+    }
+
+    match autosave_interval {
+        Some(0) => note_manager.set_autosave_interval(None),
+        Some(seconds) => note_manager.set_autosave_interval(Some(Duration::from_secs(seconds))),
+        None => (),
+    }
+
+    if let Some(autosave_actions) = autosave_actions {
+        note_manager.set_autosave_actions(autosave_actions);
+    }
+
+    if let Some(restore) = restore {
+        let result = match note_manager.find_snapshot(restore.as_str()) {
+            Some(snapshot) => note_manager.restore_snapshot(snapshot.as_str()),
+            None => Err(format!("No snapshot named {} found", restore)),
+        };
+
+        match result {
+            Ok(_) => note_manager.set_message(format!("Restored snapshot {}", restore).as_str()),
+            Err(e) => note_manager.set_message(e.as_str()),
+        }
+    }
+
+    if sync_at_startup {
+        note_manager.render();
+        note_manager.sync_with_files();
+    }
+
+    signals::init_signals();
 
-    let note_manager = note_manager::NoteManager::new(String::from("Notes"), file);
     let mut keybind_manager = keybind_manager::KeybindManager::new(note_manager);
 
     init_keybindings(&mut keybind_manager);
 
+    //In milliseconds, 0 waits for the next key like no timeout at all
+    match settings.key_timeout {
+        Some(0) | None => (),
+        Some(key_timeout) => keybind_manager.set_key_timeout(Some(Duration::from_millis(key_timeout))),
+    }
+    keybind_manager.load_keymap(&settings.keys);
+
     keybind_manager.begin();
     keybind_manager.end();
 }
@@ -1,18 +1,40 @@
 extern crate pancurses;
 extern crate json;
+extern crate libc;
 
 use pancurses::*;
+use std::fs;
 use std::fs::File;
-use self::json::*;
+use std::io;
 use std::io::prelude::*;
+use std::result::Result;
 use notes;
 use actions::*;
+use backups;
+use store;
+use store::NoteStore;
 use std::env;
+use std::time::{Duration, Instant, SystemTime};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::process;
+use signals;
+use lock;
+use sync;
+use import;
+use tags;
+use finder;
+use index;
+use search;
+use opener;
+use config;
+use std::path::Path;
+use std::rc::Rc;
 
 pub struct NoteManager {
     pub root: notes::Entry,
     title: String,
-    pub window: Window,
+    window: Option<Window>,
     pub cursor: i32,
     pub next_id: u32,
     pub actions: ActionList,
@@ -20,12 +42,46 @@ pub struct NoteManager {
     scroll: i32,
     pub running: bool,
     file: String,
+    store_kind: store::StoreKind,
+    store: Box<NoteStore>,
+    message: Option<String>,
+    backup_dir: String,
+    backup_count: usize,
+    read_only: bool,
+    saved_changes: u64,
+    last_seen_changes: u64,
+    last_change: Instant,
+    failed_autosave_changes: Option<u64>,
+    autosave_interval: Option<Duration>,
+    autosave_actions: u64,
+    lock: Option<lock::NotebookLock>,
+    loaded_modified: Option<SystemTime>,
+    loaded_hash: Option<u64>,
+    loaded_next_id: u32,
+    filter: Option<tags::TagFilter>,
+    index: Option<index::ContentIndex>,
+    opener: opener::Opener,
+    repeat: Option<(RepeatFunction, usize)>,
+    pending_keys: String,
+    exit_error: Option<String>,
+    indent: i32,
 }
 
+//A failed snapshot still leaves the notebook saved, so the two are told apart
+pub enum SaveError {
+    Save(io::Error),
+    Snapshot(io::Error),
+}
+
+//Makes the last change again at the cursor for the . command, it gets the count to use
+pub type RepeatFunction = Rc<Fn(&mut NoteManager, usize)>;
+
 impl NoteManager {
-    pub fn new(title: String, file: String) -> NoteManager {
-        let mut root = notes::EntryBuilder::new(0).set_text("root").set_is_category(true).set_full_file_location(format!("{}/", env::current_dir().unwrap().to_str().unwrap())).set_child_indent_depth(0).set_is_open(false).build();
-        root.toggle_open();
+    pub fn new(title: String, file: String, store_kind: store::StoreKind) -> NoteManager {
+        //Curses waits a second after Escape to see if it starts a special key, that makes Escape and Alt slow
+        if env::var_os("ESCDELAY").is_none() {
+            env::set_var("ESCDELAY", "25");
+        }
 
         //Create and set up the pancurses window
         let window = initscr();
@@ -44,8 +100,22 @@ impl NoteManager {
 
         window.refresh();
         window.keypad(true);
+        //Wake up periodically so autosave and signals are handled while idle
+        window.timeout(1000);
         noecho();
 
+        NoteManager::open(title, file, store_kind, Some(window))
+    }
+
+    //For command line use, messages go to stderr and prompts take their default
+    pub fn new_headless(file: String, store_kind: store::StoreKind) -> NoteManager {
+        NoteManager::open(String::from("Notes"), file, store_kind, None)
+    }
+
+    fn open(title: String, file: String, store_kind: store::StoreKind, window: Option<Window>) -> NoteManager {
+        let mut root = notes::EntryBuilder::new(0).set_text("root").set_is_category(true).set_full_file_location(format!("{}/", env::current_dir().unwrap().to_str().unwrap())).set_child_indent_depth(0).set_is_open(false).build();
+        root.toggle_open();
+
         let mut nm = NoteManager {
             root: root,
             title: title,
@@ -57,62 +127,354 @@ impl NoteManager {
             scroll: 0,
             running: true,
             file: file.clone(),
+            store_kind: store_kind,
+            store: store::open_store(file.as_str(), store_kind),
+            message: None,
+            backup_dir: backups::default_backup_dir(file.as_str()),
+            backup_count: backups::DEFAULT_BACKUP_COUNT,
+            read_only: false,
+            saved_changes: 0,
+            last_seen_changes: 0,
+            last_change: Instant::now(),
+            failed_autosave_changes: None,
+            autosave_interval: Some(Duration::from_secs(30)),
+            autosave_actions: 20,
+            lock: None,
+            loaded_modified: None,
+            loaded_hash: None,
+            loaded_next_id: 1,
+            filter: None,
+            index: None,
+            opener: opener::Opener::new(),
+            repeat: None,
+            pending_keys: String::new(),
+            exit_error: None,
+            indent: notes::DEFAULT_INDENT,
         };
 
+        match lock::NotebookLock::acquire(file.as_str()) {
+            Ok(lock) => nm.lock = Some(lock),
+            Err(lock::LockError::Held(pid)) => {
+                nm.read_only = true;
+                nm.set_message(format!("{} is already open in process {}. Opened read only", file, pid).as_str());
+            },
+            Err(lock::LockError::Io(e)) => {
+                nm.read_only = true;
+                nm.set_message(format!("Could not lock {} ({}). Opened read only", file, e).as_str());
+            },
+        }
+
         nm.load_from_file(file.as_str());
+        nm.loaded_next_id = nm.next_id;
+        nm.record_file_state();
         nm.root.child_indent_depth = 0;
 
         nm
     }    
 
+    pub fn window(&self) -> &Window {
+        self.window.as_ref().expect("no window in headless mode")
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.window.is_none()
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.title = String::from(title);
+    }
+
+    pub fn get_indent(&self) -> i32 {
+        self.indent
+    }
+
+    //The root is not drawn, so its children stay at the left edge
+    pub fn set_indent(&mut self, indent: i32) {
+        self.indent = indent;
+        self.root.set_indent(indent);
+        self.root.child_indent_depth = 0;
+    }
+
+    //Pair 1 is the normal text and pair 2 the highlighted line
+    pub fn set_colors(&self, colors: &config::Colors) {
+        if self.is_headless() || !has_colors() {
+            return;
+        }
+
+        //Nothing was configured, the pairs set up when the window was created are already right
+        if colors.foreground.is_none() && colors.background.is_none() && colors.highlight_foreground.is_none() && colors.highlight_background.is_none() {
+            return;
+        }
+
+        init_pair(1 as i16, colors.foreground.unwrap_or(COLOR_WHITE), colors.background.unwrap_or(COLOR_BLACK));
+        init_pair(2 as i16, colors.highlight_foreground.unwrap_or(COLOR_WHITE), colors.highlight_background.unwrap_or(COLOR_BLUE));
+        self.window().bkgd(COLOR_PAIR(1));
+    }
+
+    pub fn get_opener(&self) -> &opener::Opener {
+        &self.opener
+    }
+
+    pub fn set_opener(&mut self, opener: opener::Opener) {
+        self.opener = opener;
+    }
+
+    //Opens a note with its opener or toggles a category
+    pub fn activate_entry(&mut self, id: u32) {
+        let has_file = match self.root.get_child_by_id(id) {
+            Some(entry) => entry.has_file(),
+            None => return,
+        };
+
+        if has_file {
+            self.open_entry(id, None);
+        } else {
+            self.root.get_mut_child_by_id(id).unwrap().toggle_open();
+            self.render();
+        }
+    }
+
+    pub fn open_entry(&mut self, id: u32, line: Option<usize>) {
+        let result = match self.root.get_child_by_id(id) {
+            Some(entry) => self.run_external(|| entry.open_file(&self.opener, line)),
+            None => Ok(()),
+        };
+
+        self.finish_opening(id, result);
+    }
+
+    pub fn open_entry_with(&mut self, id: u32, command: &str) {
+        let result = match self.root.get_child_by_id(id) {
+            Some(entry) => self.run_external(|| entry.open_file_with(command, None)),
+            None => Ok(()),
+        };
+
+        self.finish_opening(id, result);
+    }
+
+    //Hands the terminal to another program, curses is restored and the screen redrawn once it exits
+    pub fn run_external<T, F: FnOnce() -> T>(&self, run: F) -> T {
+        if self.is_headless() {
+            return run();
+        }
+
+        def_prog_mode();
+        endwin();
+        signals::allow_stop(true);
+
+        let result = run();
+
+        signals::allow_stop(false);
+        self.restore_terminal();
+
+        result
+    }
+
+    //Ctrl-Z, leaves curses mode so the shell gets a clean terminal while we are stopped
+    pub fn suspend(&self) {
+        if self.is_headless() {
+            return;
+        }
+
+        def_prog_mode();
+        endwin();
+
+        signals::stop_process();
+
+        self.restore_terminal();
+    }
+
+    fn restore_terminal(&self) {
+        reset_prog_mode();
+        curs_set(0);
+
+        self.render();
+        self.window().refresh();
+    }
+
+    fn finish_opening(&mut self, id: u32, result: Result<(), String>) {
+        if let Some(entry) = self.root.get_child_by_id(id) {
+            entry.refresh_missing();
+        }
+
+        self.update_index(id);
+        self.save_index();
+        self.render();
+
+        if let Err(e) = result {
+            self.set_message(e.as_str());
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: Option<tags::TagFilter>) {
+        self.filter = filter;
+        self.cursor = 0;
+        self.scroll = 0;
+
+        self.refresh_filter();
+        self.render();
+    }
+
+    //Entries can appear or disappear when tags change, so the cursor has to stay inside what is shown
+    pub fn refresh_filter(&mut self) {
+        self.root.apply_filter(self.filter.as_ref());
+
+        let cursor_max = self.root.flatten_children().len() as i32;
+        if self.cursor >= cursor_max {
+            self.cursor = (cursor_max - 1).max(0);
+        }
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        }
+    }
+
+    //The number of entries that fit below the title
+    pub fn get_page_height(&self) -> i32 {
+        (self.window().get_max_y() - 2).max(1)
+    }
+
+    //Curses has already resized the window, the cursor and scroll are fitted to the new height
+    pub fn resize(&mut self) {
+        if self.is_headless() {
+            return;
+        }
+
+        let cursor_max = self.root.flatten_children().len() as i32;
+        let height = self.get_page_height();
+
+        self.cursor = self.cursor.min(cursor_max - 1).max(0);
+        if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor - height + 1;
+        }
+        self.scroll = self.clamp_scroll(self.scroll).min(self.cursor);
+
+        self.render();
+    }
+
+    //Moves stop at the first and last entry, so a count larger than the list still gets there
     pub fn move_cursor(&mut self, amt: i32) {
+        let cursor = self.cursor + amt;
+        self.set_cursor(cursor);
+    }
+
+    //Scrolls only as far as needed to show the cursor
+    pub fn set_cursor(&mut self, cursor: i32) {
         let cursor_max = self.root.flatten_children().len() as i32;
+        let cursor = cursor.min(cursor_max - 1).max(0);
 
-        if self.cursor + amt >= 0 && self.cursor + amt < cursor_max {
-            self.unhighlight_line(self.cursor - self.scroll);
+        let height = self.get_page_height();
+        let scroll = if cursor < self.scroll {
+            cursor
+        } else if cursor >= self.scroll + height {
+            cursor - height + 1
+        } else {
+            self.scroll
+        };
 
-            self.cursor += amt; 
+        self.show_cursor(cursor, scroll);
+    }
 
-            if self.cursor < self.scroll {
-                self.scroll -= 1; 
-                self.render();
-            } else if self.cursor >= self.scroll + self.window.get_max_y() - 2 {
-                self.scroll += 1; 
-                self.render();
-            }
+    pub fn set_cursor_to_entry(&mut self, id: u32) {
+        if let Some(cursor) = self.find_cursor(id) {
+            self.set_cursor(cursor);
+        }
+    }
+
+    //Moves the view and the cursor together, the cursor stops at the ends of the list and stays on screen
+    pub fn scroll_by(&mut self, lines: i32) {
+        let cursor_max = self.root.flatten_children().len() as i32;
+        let scroll = self.clamp_scroll(self.scroll + lines);
+        let cursor = (self.cursor + lines).min(cursor_max - 1).max(0);
 
-            self.highlight_line(self.cursor - self.scroll);        
+        self.show_cursor(cursor, scroll);
+    }
+
+    //Moves the view without moving the cursor unless it would go off screen
+    pub fn set_scroll(&mut self, scroll: i32) {
+        let scroll = self.clamp_scroll(scroll);
+        let cursor = self.cursor;
 
+        self.show_cursor(cursor, scroll);
+    }
+
+    //The last entry never scrolls above the bottom of the window
+    fn clamp_scroll(&self, scroll: i32) -> i32 {
+        let scroll_max = self.root.flatten_children().len() as i32 - self.get_page_height();
+        scroll.min(scroll_max).max(0)
+    }
+
+    //Only redraws everything when the view moved, otherwise just the highlight changes
+    fn show_cursor(&mut self, cursor: i32, scroll: i32) {
+        let height = self.get_page_height();
+        let cursor = cursor.min(scroll + height - 1).max(scroll);
+
+        if scroll != self.scroll {
+            self.cursor = cursor;
+            self.scroll = scroll;
+            self.render();
+        } else if cursor != self.cursor {
+            self.unhighlight_line(self.cursor - self.scroll);
+            self.cursor = cursor;
+            self.highlight_line(self.cursor - self.scroll);
         }
     }
 
+    //Remembers how to make the last change again, with the count it was made with
+    pub fn set_repeat<F: 'static>(&mut self, count: usize, function: F) where
+        F: Fn(&mut NoteManager, usize)
+    {
+        self.repeat = Some((Rc::new(function), count));
+    }
+
+    //A count given to . replaces the one the change was made with
+    pub fn repeat_last_change(&mut self, count: Option<usize>) {
+        let (function, count) = match self.repeat {
+            Some((ref function, last_count)) => (function.clone(), count.unwrap_or(last_count)),
+            None => {
+                self.display_message("There is no change to repeat");
+                return;
+            },
+        };
+
+        function(self, count);
+        self.repeat = Some((function, count));
+    }
+
     fn unhighlight_line(&self, line: i32) {
-        self.window.mv(line + 2, 0);
+        self.window().mv(line + 2, 0);
         
-        self.window.chgat(-1, A_COLOR, 1);
+        self.window().chgat(-1, A_COLOR, 1);
     }
 
     fn highlight_line(&self, line: i32) {
-        self.window.mv(line + 2, 0);
+        self.window().mv(line + 2, 0);
 
-        self.window.chgat(-1, A_COLOR, 2);
+        self.window().chgat(-1, A_COLOR, 2);
     }
 
     pub fn get_input(&self) -> Option<String> {
+        if self.is_headless() {
+            return None;
+        }
+
         let mut input = String::new();
 
         curs_set(1);
 
-        let mut ch = self.window.getch();
+        let mut ch = self.window().getch();
 
-        while ch != Some(Input::Character('\n')) {
+        while ch != Some(Input::Character('\n')) && !signals::exit_requested() {
             match ch {
                 Some(Input::Character('\t')) => (),
                 Some(Input::Character(c)) => {
                     if c == (127 as char) {
                         if !input.is_empty() {
-                            self.window.mv(self.window.get_cur_y(), self.window.get_cur_x() - 1); 
-                            self.window.delch();
+                            self.window().mv(self.window().get_cur_y(), self.window().get_cur_x() - 1); 
+                            self.window().delch();
 
                             let index = input.len() - 1;
                             input.remove(index);     
@@ -123,20 +485,21 @@ impl NoteManager {
                         break;
                     } else {
                         input.push(c); 
-                        self.window.addch(c);
+                        self.window().addch(c);
                     } 
                 }
                 _ => ()
             }
 
-            ch = self.window.getch();
+            ch = self.window().getch();
         }
         
         curs_set(0);
 
         self.render();
 
-        if input.is_empty() {
+        //Half typed text shouldn't be saved when quitting on a signal
+        if input.is_empty() || signals::exit_requested() {
             None 
         } else {
             Some(input)
@@ -169,64 +532,788 @@ impl NoteManager {
         }
     }
 
+    pub fn set_message(&mut self, msg: &str) {
+        self.message = Some(String::from(msg));
+        self.display_message(msg);
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
+    pub fn select_from_list(&self, title: &str, items: &Vec<String>) -> Option<usize> {
+        if items.is_empty() || self.is_headless() {
+            return None;
+        }
+
+        let mut selected: i32 = 0;
+        let mut scroll: i32 = 0;
+        let mut result = None;
+
+        while !signals::exit_requested() {
+            let height = self.window().get_max_y() - 2;
+
+            if selected < scroll {
+                scroll = selected;
+            } else if selected >= scroll + height {
+                scroll = selected - height + 1;
+            }
+
+            self.window().erase();
+            self.window().mv(0, 0);
+            self.window().addstr(fit_to_width(title, self.window().get_max_x()).as_str());
+            self.display_message("j/k to move, enter to select, q to cancel");
+
+            for (i, item) in items.iter().enumerate().skip(scroll as usize).take(height as usize) {
+                self.window().mv(i as i32 - scroll + 2, 0);
+                self.window().addstr(fit_to_width(item.as_str(), self.window().get_max_x()).as_str());
+            }
+            self.highlight_line(selected - scroll);
+
+            match self.window().getch() {
+                Some(Input::Character('j')) => if selected + 1 < items.len() as i32 { selected += 1 },
+                Some(Input::Character('k')) => if selected > 0 { selected -= 1 },
+                Some(Input::Character('\n')) => {
+                    result = Some(selected as usize);
+                    break;
+                },
+                Some(Input::Character('q')) | Some(Input::Character('\u{1b}')) => break,
+                _ => (),
+            }
+        }
+
+        self.render();
+
+        result
+    }
+
+    //Live updating search over every entry, returns the id of the chosen one
+    pub fn fuzzy_find(&self) -> Option<u32> {
+        if self.is_headless() {
+            return None;
+        }
+
+        let candidates = finder::collect_candidates(&self.root);
+        let mut query = String::new();
+        let mut matches = finder::search("", &candidates);
+        let mut selected: i32 = 0;
+        let mut scroll: i32 = 0;
+        let mut result = None;
+
+        curs_set(1);
+
+        while !signals::exit_requested() {
+            let height = self.window().get_max_y() - 2;
+
+            if selected < scroll {
+                scroll = selected;
+            } else if selected >= scroll + height {
+                scroll = selected - height + 1;
+            }
+
+            self.window().erase();
+
+            self.display_message(format!("{}/{} entries, up/down or ctrl-n/ctrl-p to move, enter to select, esc to cancel", matches.len(), candidates.len()).as_str());
+
+            for (i, &candidate) in matches.iter().enumerate().skip(scroll as usize).take(height as usize) {
+                self.window().mv(i as i32 - scroll + 2, 0);
+                self.window().addstr(fit_to_width(candidates[candidate].text.as_str(), self.window().get_max_x()).as_str());
+            }
+            if !matches.is_empty() {
+                self.highlight_line(selected - scroll);
+            }
+
+            self.window().mv(0, 0);
+            self.window().addstr(fit_to_width(format!("Find: {}", query).as_str(), self.window().get_max_x()).as_str());
+
+            let mut query_changed = false;
+
+            match self.window().getch() {
+                Some(Input::KeyDown) | Some(Input::Character('\u{e}')) => if selected + 1 < matches.len() as i32 { selected += 1 },
+                Some(Input::KeyUp) | Some(Input::Character('\u{10}')) => if selected > 0 { selected -= 1 },
+                Some(Input::Character('\n')) => {
+                    result = matches.get(selected as usize).map(|&candidate| candidates[candidate].id);
+                    break;
+                },
+                Some(Input::Character('\u{1b}')) => break,
+                Some(Input::KeyBackspace) | Some(Input::Character('\u{7f}')) => {
+                    query_changed = query.pop().is_some();
+                },
+                Some(Input::Character(c)) if !c.is_control() => {
+                    query.push(c);
+                    query_changed = true;
+                },
+                _ => (),
+            }
+
+            if query_changed {
+                matches = finder::search(query.as_str(), &candidates);
+                selected = 0;
+                scroll = 0;
+            }
+        }
+
+        curs_set(0);
+        self.render();
+
+        result
+    }
+
+    //Opens every category above the entry and puts the cursor on it
+    pub fn reveal_entry(&mut self, id: u32) {
+        let mut ancestor = self.root.get_parent_by_id(id).map(|parent| parent.get_id());
+        while let Some(ancestor_id) = ancestor {
+            self.root.get_mut_child_by_id(ancestor_id).unwrap().set_is_open(true);
+            ancestor = self.root.get_parent_by_id(ancestor_id).map(|parent| parent.get_id());
+        }
+
+        self.root.apply_filter(self.filter.as_ref());
+        if self.filter.is_some() && self.find_cursor(id).is_none() {
+            self.filter = None;
+            self.root.apply_filter(None);
+            self.set_message("The tag filter was cleared to show the entry");
+        }
+
+        if let Some(cursor) = self.find_cursor(id) {
+            self.cursor = cursor;
+
+            if !self.is_headless() {
+                let height = self.get_page_height();
+                if self.cursor < self.scroll || self.cursor >= self.scroll + height {
+                    self.scroll = self.clamp_scroll(self.cursor - height / 2);
+                }
+            }
+        }
+
+        self.render();
+    }
+
+    fn find_cursor(&self, id: u32) -> Option<i32> {
+        self.root.flatten_children().iter().position(|entry| entry.get_id() == id).map(|position| position as i32)
+    }
+
     pub fn display_message(&self, msg: &str) {
-        self.window.mv(1, 0);
-        self.window.clrtoeol();
-        self.window.addstr(msg);
+        if self.is_headless() {
+            eprintln!("{}", msg);
+            return;
+        }
+
+        self.window().mv(1, 0);
+        self.window().clrtoeol();
+        self.window().addstr(fit_to_width(msg, self.window().get_max_x()).as_str());
+    }
+
+    pub fn render_title(&self) {
+        if self.is_headless() {
+            return;
+        }
+
+        self.window().mv(0, 0);
+        self.window().clrtoeol();
+
+        let mut title = self.title.clone();
+        if self.is_dirty() {
+            title.push_str(" [+]");
+        }
+        if self.read_only {
+            title.push_str(" [read only]");
+        }
+        if let Some(ref filter) = self.filter {
+            title.push_str(format!(" [filter: {}]", filter.get_text()).as_str());
+        }
+
+        //Keys typed so far go at the right edge, like vim's showcmd, the title is cut short to make room
+        let max_x = self.window().get_max_x();
+        let pending_width = display_width(self.pending_keys.as_str());
+        let title_width = match pending_width {
+            0 => max_x,
+            _ => max_x - pending_width - 2,
+        };
+
+        self.window().addstr(fit_to_width(title.as_str(), title_width).as_str());
+        if pending_width > 0 && pending_width < max_x {
+            self.window().mv(0, max_x - pending_width - 1);
+            self.window().addstr(self.pending_keys.as_str());
+        }
+    }
+
+    pub fn set_pending_keys(&mut self, pending_keys: &str) {
+        self.pending_keys = String::from(pending_keys);
+    }
+
+    //Lists the keys that can follow the ones typed so far at the bottom of the screen, it stays until the next render
+    pub fn show_key_hints(&self, pending: &str, hints: &Vec<(String, String)>) {
+        if self.is_headless() || hints.is_empty() {
+            return;
+        }
+
+        let max_y = self.window().get_max_y();
+        let max_x = self.window().get_max_x();
+        let rows = (hints.len() as i32).min(max_y - 3).max(1);
+        let key_width = hints.iter().map(|&(ref keys, _)| keys.chars().count()).max().unwrap_or(0);
+        let top = max_y - rows - 1;
+
+        self.window().mv(top, 0);
+        self.window().clrtoeol();
+        self.window().addstr(fit_to_width(format!("{} (esc to cancel)", pending).as_str(), max_x).as_str());
+        self.window().mv(top, 0);
+        self.window().chgat(-1, A_COLOR, 2);
+
+        for (i, &(ref keys, ref description)) in hints.iter().take(rows as usize).enumerate() {
+            let mut line = format!("  {:width$}  {}", keys, description, width = key_width);
+            if i as i32 == rows - 1 && hints.len() > rows as usize {
+                line = format!("  ... {} more", hints.len() - i);
+            }
+
+            self.window().mv(top + 1 + i as i32, 0);
+            self.window().clrtoeol();
+            self.window().addstr(take_width(line.as_str(), max_x - 1).as_str());
+        }
+
+        self.window().refresh();
     }
 
     pub fn render(&self) {
-        self.window.mv(0, 0);
-        self.window.clear();
+        if self.is_headless() {
+            return;
+        }
+
+        self.root.apply_filter(self.filter.as_ref());
+
+        //erase only redraws what changed, clear repaints the whole terminal and flickers
+        self.window().mv(0, 0);
+        self.window().erase();
 
-        self.window.addstr(&self.title);
-        self.root.render_children(&self.window, 0, 1, self.scroll); 
+        self.render_title();
+        self.root.render_children(self.window(), 0, 1, self.scroll); 
         if self.root.flatten_children().len() > 0 {
             self.highlight_line(self.cursor - self.scroll);
         }
+
+        if let Some(ref message) = self.message {
+            self.display_message(message.as_str());
+        }
     }
 
     pub fn load_from_file(&mut self, file_name: &str) {
-        match File::open(file_name) {
-            Ok(mut file) => {
-                let mut contents = String::new();
+        let loaded = self.store.load();
+
+        match loaded {
+            Ok(Some((root, next_id))) => self.set_notebook(root, next_id),
+            Ok(None) if self.is_headless() => eprintln!("No note file found. One will be created"),
+            Ok(None) => println!("No note file found. One will be created"),
+            Err(e) => {
+                let backup = store::backup_file_name(file_name);
 
-                match file.read_to_string(&mut contents) {
-                    Ok(_) => {
-                        let (mut root, next_id) = match json::parse(contents.as_str()) {
-                            Ok(contents_json) => (notes::Entry::build_entry_from_json(&contents_json["root"]), contents_json["next_id"].as_u32().unwrap()),
-                            Err(_) => (notes::EntryBuilder::new(0).set_text("root").set_is_category(true).set_full_file_location(format!("{}/", env::current_dir().unwrap().to_str().unwrap())).set_child_indent_depth(0).set_is_open(false).build(), 1)
-                        }; 
+                //The notebook is unreadable, so fall back to the copy kept by the last good save
+                match store::open_store(backup.as_str(), self.store_kind).load() {
+                    Ok(Some((root, next_id))) => {
+                        let corrupt = format!("{}.corrupt", file_name);
 
-                        root.set_is_open(true);
+                        if self.read_only {
+                            self.set_notebook(root, next_id);
+                            self.set_message(format!("{} could not be read ({}). Showing {} read only", file_name, e, backup).as_str());
+                            return;
+                        }
 
-                        self.root = root;
-                        self.next_id = next_id;
+                        match fs::copy(file_name, corrupt.as_str()).and_then(|_| fs::copy(backup.as_str(), file_name)) {
+                            Ok(_) => {
+                                self.set_notebook(root, next_id);
+                                self.set_message(format!("{} could not be read ({}). Restored from {}, the unreadable file was kept as {}", file_name, e, backup, corrupt).as_str());
+                            },
+                            Err(copy_error) => {
+                                self.read_only = true;
+                                self.set_message(format!("{} could not be read ({}) and restoring {} failed ({}). Opened read only", file_name, e, backup, copy_error).as_str());
+                            },
+                        }
+                    },
+                    _ => {
+                        self.read_only = true;
+                        self.set_message(format!("{} could not be read ({}). Opened read only so it is not overwritten", file_name, e).as_str());
                     },
-                    Err(_) => ()
+                }
+            }
+        }
+    }
+
+    pub fn set_notebook(&mut self, mut root: notes::Entry, next_id: u32) {
+        root.set_is_open(true);
+        root.set_indent(self.indent);
+        root.child_indent_depth = 0;
+
+        self.root = root;
+        self.next_id = next_id;
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    pub fn set_backup_dir(&mut self, backup_dir: &str) {
+        self.backup_dir = String::from(backup_dir);
+    }
+
+    pub fn set_backup_count(&mut self, backup_count: usize) {
+        self.backup_count = backup_count;
+    }
+
+    pub fn get_snapshots(&self) -> Vec<String> {
+        backups::list_snapshots(self.file.as_str(), self.backup_dir.as_str())
+    }
+
+    pub fn find_snapshot(&self, snapshot: &str) -> Option<String> {
+        backups::find_snapshot(self.file.as_str(), self.backup_dir.as_str(), snapshot)
+    }
+
+    pub fn restore_snapshot(&mut self, snapshot: &str) -> Result<(), String> {
+        match store::open_store(snapshot, self.store_kind).load() {
+            Ok(Some((root, next_id))) => {
+                let restore = RestoreAction::new(self, root, next_id);
+                restore.activate(self);
+
+                self.actions.add(Box::new(restore));
+
+                Ok(())
+            },
+            Ok(None) => Err(format!("{} does not exist", snapshot)),
+            Err(e) => Err(format!("{} could not be read: {}", snapshot, e)),
+        }
+    }
+
+    pub fn write_to_store(&mut self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the notebook is open read only"));
+        }
+
+        self.store.save(&self.root, self.next_id)
+    }
+
+    pub fn mark_changed(&mut self, id: u32) {
+        self.store.entry_changed(id);
+    }
+
+    pub fn mark_notebook_changed(&mut self) {
+        self.store.notebook_changed();
+    }
+
+    pub fn set_autosave_interval(&mut self, autosave_interval: Option<Duration>) {
+        self.autosave_interval = autosave_interval;
+    }
+
+    pub fn set_autosave_actions(&mut self, autosave_actions: u64) {
+        self.autosave_actions = autosave_actions;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.actions.get_changes() != self.saved_changes
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        self.write_to_store()?;
+        self.saved_changes = self.actions.get_changes();
+        self.loaded_next_id = self.next_id;
+        self.record_file_state();
+        self.save_index();
+
+        Ok(())
+    }
+
+    //The index is only loaded once it is needed for a search
+    pub fn search_contents(&mut self, query: &search::Query) -> Vec<search::SearchResult> {
+        //Notes may have been edited while we were not running, so every file is checked once when the index is loaded
+        if self.index.is_none() {
+            let mut index = index::ContentIndex::load(index::index_file_name(self.file.as_str()).as_str());
+            index.update(&self.root);
+
+            self.index = Some(index);
+            self.save_index();
+        }
+
+        self.index.as_ref().unwrap().search(&self.root, query)
+    }
+
+    //Called whenever the notes under an entry may have changed, does nothing until a search loads the index
+    pub fn update_index(&mut self, id: u32) {
+        if let Some(ref mut index) = self.index {
+            index.update_entry(&self.root, id);
+        }
+    }
+
+    pub fn save_index(&mut self) {
+        let result = match self.index {
+            Some(ref mut index) => {
+                //Another process owns the notebook, so the index is only kept in memory
+                if self.read_only {
+                    Ok(())
+                } else {
+                    index.save()
                 }
             },
-            Err(_) => println!("No note file found. One will be created")
+            None => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.display_message(format!("Could not save the search index: {}", e).as_str());
+        }
+    }
+
+    pub fn rebuild_index(&mut self) -> io::Result<usize> {
+        let mut index = index::ContentIndex::new(index::index_file_name(self.file.as_str()).as_str());
+        index.update(&self.root);
+        if !self.read_only {
+            index.save()?;
         }
+
+        let num_files = index.num_files();
+        self.index = Some(index);
+
+        Ok(num_files)
+    }
+
+    fn record_file_state(&mut self) {
+        self.loaded_modified = file_modified(self.file.as_str());
+        self.loaded_hash = file_hash(self.file.as_str());
     }
 
-    pub fn write_json_to_file(&self) {
-        let json_output = object! {
-            "next_id" => self.next_id,
-            "root" => self.root.get_as_json(),
+    pub fn changed_on_disk(&self) -> bool {
+        let modified = file_modified(self.file.as_str());
+
+        if modified == self.loaded_modified {
+            return false;
+        }
+
+        //The file was touched, only treat it as changed if the contents differ
+        file_hash(self.file.as_str()) != self.loaded_hash
+    }
+
+    //Adds entries created by another program since we loaded the file. Edits to entries we already have are not merged
+    pub fn merge_from_disk(&mut self) -> Result<usize, String> {
+        let (disk_root, disk_next_id) = match self.store.load() {
+            Ok(Some(notebook)) => notebook,
+            Ok(None) => return Ok(0),
+            Err(e) => return Err(e),
         };
 
-        let file_contents = json::stringify_pretty(json_output, 2);
-        
-        let mut file = File::create(self.file.as_str()).unwrap();
+        //Loading forgot what we changed, our version of every entry has to be written over the one on disk
+        self.store.notebook_changed();
+
+        let mut additions = Vec::new();
+        collect_new_entries(&disk_root, self.loaded_next_id, &mut additions);
+
+        let mut next_id = self.next_id.max(disk_next_id);
+        let merged = additions.len();
+
+        for (parent_id, mut entry) in additions {
+            entry.remap_ids(self.loaded_next_id, &mut next_id);
+
+            let parent_id = match self.root.get_child_by_id(parent_id) {
+                Some(parent) if parent.can_add_child() => parent_id,
+                _ => self.root.get_id(),
+            };
+
+            let add = AddAction::new(parent_id, entry);
+            add.activate(self);
+
+            self.actions.add(Box::new(add));
+        }
+
+        self.next_id = self.next_id.max(next_id);
+        self.loaded_next_id = self.next_id;
+        self.record_file_state();
+
+        Ok(merged)
+    }
+
+    //Returns true if it is fine to write the notebook
+    fn resolve_external_change(&mut self) -> bool {
+        if !self.changed_on_disk() {
+            return true;
+        }
+
+        loop {
+            let choice = self.get_text_input(format!("{} changed on disk. (m)erge, (o)verwrite or (a)bort", self.file).as_str());
+
+            match choice.as_ref().map(|choice| choice.to_lowercase()) {
+                Some(ref choice) if choice.starts_with("m") => {
+                    return match self.merge_from_disk() {
+                        Ok(merged) => {
+                            self.set_message(format!("Merged {} new entries from disk", merged).as_str());
+                            true
+                        },
+                        Err(e) => {
+                            self.set_message(format!("Merge failed, {} could not be read: {}", self.file, e).as_str());
+                            false
+                        },
+                    };
+                },
+                Some(ref choice) if choice.starts_with("o") => return true,
+                Some(ref choice) if !choice.starts_with("a") => continue,
+                _ => return false,
+            }
+        }
+    }
+
+    pub fn autosave(&mut self) {
+        let changes = self.actions.get_changes();
+
+        if changes != self.last_seen_changes {
+            self.last_seen_changes = changes;
+            self.last_change = Instant::now();
+        }
+
+        //Don't retry a failed autosave until something else changes
+        if !self.is_dirty() || self.read_only || self.failed_autosave_changes == Some(changes) {
+            return;
+        }
+
+        let idle = match self.autosave_interval {
+            Some(interval) => self.last_change.elapsed() >= interval,
+            None => false,
+        };
+        let enough_actions = self.autosave_actions > 0 && changes - self.saved_changes >= self.autosave_actions;
+
+        if !idle && !enough_actions {
+            return;
+        }
+
+        if self.changed_on_disk() {
+            self.failed_autosave_changes = Some(changes);
+            self.set_message(format!("{} changed on disk, autosave is paused until you quit with q and choose what to keep", self.file).as_str());
+        } else if let Err(e) = self.save() {
+            self.failed_autosave_changes = Some(changes);
+            self.set_message(format!("Autosave failed: {}", e).as_str());
+        }
 
-        file.write_all(file_contents.as_bytes());
+        self.render_title();
+    }
+
+    pub fn save_with_snapshot(&mut self) -> Result<(), SaveError> {
+        self.save().map_err(SaveError::Save)?;
+        backups::create_snapshot(self.file.as_str(), self.backup_dir.as_str(), self.backup_count).map_err(SaveError::Snapshot)?;
+
+        Ok(())
     }
 
     pub fn end(&mut self) {
-        endwin(); 
+        if self.read_only {
+            self.running = false;
+            return;
+        }
+
+        if !self.resolve_external_change() {
+            return;
+        }
+
+        match self.save_with_snapshot() {
+            Ok(_) => self.running = false,
+            Err(SaveError::Snapshot(e)) => {
+                self.exit_error = Some(format!("Saved {}, but the snapshot failed ({})", self.file, e));
+                self.running = false;
+            },
+            Err(SaveError::Save(e)) => {
+                if self.get_bool_input(format!("Failed to save {} ({}). Quit without saving?", self.file, e).as_str(), false) {
+                    self.running = false;
+                }
+            }
+        }
+    }
+
+    //Walks the user through entries whose files are gone and files no entry points at
+    pub fn sync_with_files(&mut self) {
+        self.root.refresh_missing();
+
+        let notebook_path = match env::current_dir() {
+            Ok(directory) => directory.join(self.file.as_str()).to_string_lossy().into_owned(),
+            Err(_) => self.file.clone(),
+        };
+        let report = sync::scan(&self.root, &vec![notebook_path]);
+
+        if report.is_empty() {
+            self.set_message("Notebook and files are in sync");
+            return;
+        }
+
+        let mut sync_actions: Vec<Box<Action>> = Vec::new();
+        let mut next_id = self.next_id;
+        let mut adopt_all = false;
+
+        'missing: for id in report.missing.iter() {
+            let (text, file_location) = match self.root.get_child_by_id(*id) {
+                Some(entry) => (entry.text.clone(), entry.file_location.clone()),
+                None => continue,
+            };
+
+            loop {
+                let choice = self.get_text_input(format!("{} is missing {}. (p)rune, (r)elink, (s)kip or (q)uit", text, file_location).as_str());
+
+                match choice.as_ref().map(|choice| choice.to_lowercase()) {
+                    Some(ref choice) if choice.starts_with("p") => {
+                        sync_actions.push(Box::new(DeleteAction::new(*id)));
+                        break;
+                    },
+                    Some(ref choice) if choice.starts_with("r") => {
+                        if let Some(new_location) = self.get_text_input("Input new file location") {
+                            let change_file = ChangeFileAction::new(self.root.get_child_by_id(*id).unwrap(), new_location.as_str());
+                            sync_actions.push(Box::new(change_file));
+                        }
+                        break;
+                    },
+                    Some(ref choice) if choice.starts_with("q") => break 'missing,
+                    Some(ref choice) if !choice.starts_with("s") => continue,
+                    _ => break,
+                }
+            }
+        }
+
+        'untracked: for &(category_id, ref path) in report.untracked.iter() {
+            let category_name = self.root.get_child_by_id(category_id).unwrap().text.clone();
+
+            if !adopt_all {
+                loop {
+                    let choice = self.get_text_input(format!("{} is not in the notebook. Adopt into {}? (y)es, (n)o, (a)ll or (q)uit", path, category_name).as_str());
+
+                    match choice.as_ref().map(|choice| choice.to_lowercase()) {
+                        Some(ref choice) if choice.starts_with("y") => break,
+                        Some(ref choice) if choice.starts_with("a") => {
+                            adopt_all = true;
+                            break;
+                        },
+                        Some(ref choice) if choice.starts_with("q") => break 'untracked,
+                        Some(ref choice) if !choice.starts_with("n") => continue,
+                        _ => continue 'untracked,
+                    }
+                }
+            }
+
+            let path = Path::new(path.as_str());
+            let entry = if path.is_dir() {
+                match import::import_directory(path.to_string_lossy().as_ref(), &import::ImportOptions::new(), &mut next_id) {
+                    Ok(entry) => entry,
+                    Err(_) => continue,
+                }
+            } else {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                import::build_entry(path, name.as_str(), false, &mut next_id)
+            };
+
+            sync_actions.push(Box::new(AddAction::new(category_id, entry)));
+        }
+
+        let sync_action = GroupAction::new(sync_actions);
+
+        if !sync_action.is_empty() {
+            sync_action.activate(self);
+            self.actions.add(Box::new(sync_action));
+        }
+
+        self.render();
+    }
+
+    //Used when a signal asks us to exit, there is no one left to answer a prompt so a failed save is reported once curses is closed
+    pub fn flush(&mut self) {
+        if !self.read_only && self.is_dirty() {
+            if self.changed_on_disk() {
+                //Keep both versions rather than overwriting changes made by someone else
+                let conflict = format!("{}.{}.conflict", self.file, process::id());
+                if let Err(e) = store::JsonStore::new(conflict.as_str()).save(&self.root, self.next_id) {
+                    self.exit_error = Some(format!("Failed to save changes to {} ({})", conflict, e));
+                }
+            } else {
+                match self.save_with_snapshot() {
+                    Ok(_) => (),
+                    Err(SaveError::Save(e)) => self.exit_error = Some(format!("Failed to save {} ({})", self.file, e)),
+                    Err(SaveError::Snapshot(e)) => self.exit_error = Some(format!("Saved {}, but the snapshot failed ({})", self.file, e)),
+                }
+            }
+        }
 
-        self.write_json_to_file();
         self.running = false;
     }
+
+    pub fn close(&mut self) {
+        if !self.is_headless() {
+            endwin();
+        }
+
+        if let Some(ref exit_error) = self.exit_error {
+            eprintln!("{}", exit_error);
+        }
+
+        self.lock = None;
+    }
+}
+
+fn collect_new_entries(entry: &notes::Entry, min_id: u32, additions: &mut Vec<(u32, notes::Entry)>) {
+    for child in entry.get_children() {
+        if child.get_id() >= min_id {
+            additions.push((entry.get_id(), child.clone()));
+        } else {
+            collect_new_entries(child, min_id, additions);
+        }
+    }
+}
+
+fn file_modified(file_name: &str) -> Option<SystemTime> {
+    match fs::metadata(file_name) {
+        Ok(metadata) => metadata.modified().ok(),
+        Err(_) => None,
+    }
+}
+
+fn file_hash(file_name: &str) -> Option<u64> {
+    let mut contents = Vec::new();
+
+    match File::open(file_name) {
+        Ok(mut file) => {
+            if file.read_to_end(&mut contents).is_err() {
+                return None;
+            }
+        },
+        Err(_) => return None,
+    }
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write(contents.as_slice());
+
+    Some(hasher.finish())
+}
+
+extern "C" {
+    fn wcwidth(c: libc::wchar_t) -> libc::c_int;
+}
+
+//Columns a character takes up on the screen, wide characters like CJK take two and combining ones none
+fn char_width(c: char) -> i32 {
+    match unsafe { wcwidth(c as libc::wchar_t) } {
+        width if width >= 0 => width,
+        //Not printable in this locale, curses still puts something there
+        _ => 1,
+    }
+}
+
+pub fn display_width(text: &str) -> i32 {
+    text.chars().map(char_width).sum()
+}
+
+//The longest start of text that fits in width columns
+fn take_width(text: &str, width: i32) -> String {
+    let mut used = 0;
+
+    text.chars().take_while(|&c| {
+        used += char_width(c);
+        used <= width
+    }).collect()
+}
+
+//Lines that don't fit would wrap onto the next row, so they are cut short with an ellipsis
+pub fn fit_to_width(text: &str, width: i32) -> String {
+    if width <= 0 {
+        return String::new();
+    }
+
+    if display_width(text) <= width {
+        return String::from(text);
+    }
+
+    let mut fitted = take_width(text, width - 1);
+    fitted.push('\u{2026}');
+    fitted
 }
@@ -4,10 +4,19 @@ extern crate json;
 use pancurses::*;
 use self::json::*;
 use std::borrow::BorrowMut;
-use std::process::Command;
+use std::cmp::Ordering;
 use chrono::prelude::*;
 use note_manager;
+use sync;
 use std::fs;
+use std::mem;
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use tags;
+use opener;
+use std::result::Result;
+
+pub const DEFAULT_INDENT: i32 = 4;
 
 #[derive(Clone)]
 pub enum SortType {
@@ -28,6 +37,11 @@ pub struct Entry {
     pub time_created: DateTime<Local>, 
     sort_type: SortType,
     sort_descending: bool,
+    tags: BTreeSet<String>,
+    filtered_out: Cell<bool>,
+    filter_expanded: Cell<bool>,
+    //Whether the file is gone, only checked when the entry is first drawn
+    missing: Cell<Option<bool>>,
 }
 
 pub struct EntryBuilder {
@@ -41,6 +55,8 @@ pub struct EntryBuilder {
     sort_type: SortType,
     sort_descending: bool,
     open: bool,
+    tags: BTreeSet<String>,
+    create_directories: bool,
 }
 
 impl EntryBuilder {
@@ -50,12 +66,14 @@ impl EntryBuilder {
             is_category: false,
             text: String::new(),
             children: Vec::new(),
-            child_indent_depth: 4,
+            child_indent_depth: DEFAULT_INDENT,
             file_location: String::from(""),
             time_created: Local::now(),
             sort_type: SortType::NAME,
             sort_descending: false,
             open: false,
+            tags: BTreeSet::new(),
+            create_directories: true,
         } 
     }
 
@@ -113,11 +131,25 @@ impl EntryBuilder {
         self
     }
 
+    pub fn set_tags(mut self, tags: BTreeSet<String>) -> EntryBuilder {
+        self.tags = tags;
+        self
+    }
+
+    //Imported and loaded locations can come from outside the notebook, so nothing is created for them
+    pub fn set_create_directories(mut self, create_directories: bool) -> EntryBuilder {
+        self.create_directories = create_directories;
+        self
+    }
+
     pub fn build(self) -> Entry {
-        if self.is_category {
-            fs::create_dir_all(self.file_location.as_str()); 
-        } else {
-            fs::create_dir_all(&self.file_location[0..self.file_location.rfind('/').unwrap()]);
+        //Entries can also point at urls or nothing at all, only local paths get directories
+        if self.create_directories && sync::is_local_path(self.file_location.as_str()) {
+            if self.is_category {
+                fs::create_dir_all(self.file_location.as_str()); 
+            } else {
+                fs::create_dir_all(&self.file_location[0..self.file_location.rfind('/').unwrap()]);
+            }
         }
 
         Entry {
@@ -132,6 +164,10 @@ impl EntryBuilder {
             time_created: self.time_created,
             sort_type: self.sort_type,
             sort_descending: self.sort_descending,
+            tags: self.tags,
+            filtered_out: Cell::new(false),
+            filter_expanded: Cell::new(false),
+            missing: Cell::new(None),
         } 
     }
 }
@@ -150,32 +186,43 @@ impl Entry {
         if y < window.get_max_y() + scroll && (y - 2) >= scroll { 
             window.mv(y - scroll, x);
 
-            if self.num_children() > 0 {
-                if self.is_open {
-                    window.addstr("[-]"); 
+            let mut line = String::new();
+            if self.get_visible_children().len() > 0 {
+                if self.is_shown_open() {
+                    line.push_str("[-]"); 
                 } else { 
-                    window.addstr("[+]"); 
+                    line.push_str("[+]"); 
                 }
             }
 
-            window.addstr(&self.text);
+            line.push_str(&self.text);
             if self.file_location.as_str() != "" {
-                window.addstr(": ");
-                window.addstr(get_file_name(&self.file_location).as_str());
+                line.push_str(": ");
+                line.push_str(get_file_name(&self.file_location).as_str());
+
+                if self.is_missing() {
+                    line.push_str(" [missing]");
+                }
+            }
+
+            for tag in self.tags.iter() {
+                line.push_str(format!(" #{}", tag).as_str());
             }
+
+            window.addstr(note_manager::fit_to_width(line.as_str(), window.get_max_x() - x).as_str());
         }
 
-        if self.num_children() > 0 && self.is_open {
+        if self.get_visible_children().len() > 0 && self.is_shown_open() {
             self.render_children(window, x, y, scroll);
         }
     }
 
     pub fn render_children(&self, window: &Window, x: i32, y: i32, scroll: i32) {
-        if self.num_children() > 0 {
+        if self.get_visible_children().len() > 0 {
             let mut y_off = 1;
-            for (i, child) in self.get_children().iter().enumerate() {
+            for (i, child) in self.get_visible_children().iter().enumerate() {
                 if i > 0 {
-                    y_off += self.get_children()[i - 1].flatten_children().len() as i32;
+                    y_off += self.get_visible_children()[i - 1].flatten_children().len() as i32;
                 }
 
                 child.render_entry(window, x + self.child_indent_depth, y + (i as i32) + y_off, scroll);
@@ -183,6 +230,35 @@ impl Entry {
         }
     }
 
+    pub fn is_missing(&self) -> bool {
+        match self.missing.get() {
+            Some(missing) => missing,
+            None => {
+                let missing = sync::is_missing(self);
+                self.missing.set(Some(missing));
+                missing
+            },
+        }
+    }
+
+    //Files can come and go behind our back, this makes the next render look at them again
+    pub fn refresh_missing(&self) {
+        self.missing.set(None);
+
+        for child in self.children.iter() {
+            child.refresh_missing();
+        }
+    }
+
+    //Indents the children of this entry and of everything below it
+    pub fn set_indent(&mut self, indent: i32) {
+        self.child_indent_depth = indent;
+
+        for child in self.children.iter_mut() {
+            child.set_indent(indent);
+        }
+    }
+
     pub fn toggle_open(&mut self) {
         self.is_open = !self.is_open; 
     }
@@ -191,29 +267,108 @@ impl Entry {
         self.is_open = is_open; 
     }
 
-    pub fn open_file(&self) {
-        let mut child = Command::new("vim")
-                        .arg(self.file_location.as_str())
-                        .spawn()
-                        .expect("failed to execute process");
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    //Opens this category and the categories inside it, levels deep
+    pub fn open_levels(&mut self, levels: usize) {
+        if !self.is_category || levels == 0 {
+            return;
+        }
+
+        self.is_open = true;
+        for child in self.children.iter_mut() {
+            child.open_levels(levels - 1);
+        }
+    }
+
+    //While a filter is active categories holding matches are shown open
+    fn is_shown_open(&self) -> bool {
+        self.is_open || self.filter_expanded.get()
+    }
+
+    fn is_visible(&self) -> bool {
+        !self.is_deleted && !self.filtered_out.get()
+    }
+
+    //Hides every entry that doesn't match the filter and has no matching children, returns if this entry is still shown
+    pub fn apply_filter(&self, filter: Option<&tags::TagFilter>) -> bool {
+        let mut has_visible_child = false;
+
+        for child in self.children.iter() {
+            if child.apply_filter(filter) && !child.is_deleted {
+                has_visible_child = true;
+            }
+        }
+
+        let visible = match filter {
+            Some(filter) => filter.matches(&self.tags) || has_visible_child,
+            None => true,
+        };
+
+        self.filtered_out.set(!visible);
+        self.filter_expanded.set(filter.is_some() && has_visible_child);
+
+        visible
+    }
+
+    pub fn get_tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub fn add_tag(&mut self, tag: &str) {
+        self.tags.insert(String::from(tag));
+    }
 
-        child.wait();
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.remove(tag);
+    }
 
-        curs_set(1);
-        curs_set(0);
+    pub fn open_file(&self, opener: &opener::Opener, line: Option<usize>) -> Result<(), String> {
+        opener.open(self.file_location.as_str(), line)
+    }
+
+    pub fn open_file_with(&self, command: &str, line: Option<usize>) -> Result<(), String> {
+        opener::run_command(command, self.file_location.as_str(), line)
+    }
 
+    pub fn has_file(&self) -> bool {
+        !self.is_category && self.file_location.as_str() != ""
     }
 
     pub fn get_id(&self) -> u32 {
         self.id 
     }
 
+    pub fn get_max_id(&self) -> u32 {
+        let mut max_id = self.id;
+
+        for child in self.children.iter() {
+            max_id = max_id.max(child.get_max_id());
+        }
+
+        max_id
+    }
+
+    //Gives every entry in this subtree with an id of at least min_id a new id taken from next_id
+    pub fn remap_ids(&mut self, min_id: u32, next_id: &mut u32) {
+        if self.id >= min_id {
+            self.id = *next_id;
+            *next_id += 1;
+        }
+
+        for child in self.children.iter_mut() {
+            child.remap_ids(min_id, next_id);
+        }
+    }
+
     pub fn flatten_children<'a>(&self) -> Vec<&Entry> {
         let mut flattened_children: Vec<&Entry> = Vec::new();
 
-        if self.is_open && !self.is_deleted {
+        if self.is_shown_open() && !self.is_deleted {
             for child in self.children.iter() {
-                if !child.is_deleted {
+                if child.is_visible() {
                     flattened_children.push(child); 
                     flattened_children.append(child.flatten_children().borrow_mut());
                 }
@@ -235,6 +390,18 @@ impl Entry {
         children
     }
 
+    pub fn get_visible_children(&self) -> Vec<&Entry> {
+        let mut children: Vec<&Entry> = Vec::new();
+
+        for child in self.children.iter() {
+            if child.is_visible() {
+                children.push(child); 
+            } 
+        }
+
+        children
+    }
+
     pub fn get_children_mut(&mut self) -> Vec<&mut Entry> {
         let mut children: Vec<&mut Entry> = Vec::new();
 
@@ -264,7 +431,7 @@ impl Entry {
             return Some(self); 
         } 
 
-        for child in self.get_children().iter() {
+        for child in self.get_visible_children().iter() {
             if (n - (child.flatten_children().len() as i32) - 1) < 0 {
                 return child.get_nth_child(n - 1); 
             } else {
@@ -337,27 +504,72 @@ impl Entry {
         }
     }
 
-    pub fn build_entry_from_json(json_content: &json::JsonValue) -> Entry {
-        let text = json_content["text"].as_str().unwrap();
-        let mut file_location = "";
-        let id = json_content["id"].as_u32().unwrap();
-        let sort_type = get_sort_type_from_int(json_content["sort_type"].as_i8().unwrap());
+    pub fn build_entry_from_json(json_content: &json::JsonValue) -> Result<Entry, String> {
+        Entry::build_entry_from_json_at(json_content, "root")
+    }
+
+    fn build_entry_from_json_at(json_content: &json::JsonValue, path: &str) -> Result<Entry, String> {
+        if !json_content.is_object() {
+            return Err(format!("entry {} is not an object", path));
+        }
+
+        let id = match json_content["id"].as_u32() {
+            Some(id) => id,
+            None => return Err(format!("entry {}: field `id` is missing or not a number", path)),
+        };
+
+        let field_error = |field: &str, expected: &str| format!("entry {} (id {}): field `{}` is missing or not {}", path, id, field, expected);
+
+        let text = match json_content["text"].as_str() {
+            Some(text) => text,
+            None => return Err(field_error("text", "a string")),
+        };
+        let sort_type = match json_content["sort_type"].as_i8() {
+            Some(sort_type) => get_sort_type_from_int(sort_type),
+            None => return Err(field_error("sort_type", "a number")),
+        };
         let sort_descending = try_unwrap(json_content["sort_descending"].as_bool(), false);
-        let time_created = json_content["time_created"].as_i64().unwrap();
-        let is_category = json_content["is_category"].as_bool().unwrap();
+        let time_created = match json_content["time_created"].as_i64() {
+            Some(time_created) => time_created,
+            None => return Err(field_error("time_created", "a number")),
+        };
+        let is_category = match json_content["is_category"].as_bool() {
+            Some(is_category) => is_category,
+            None => return Err(field_error("is_category", "a boolean")),
+        };
 
+        let mut file_location = "";
         if json_content["file_location"] != json::Null {
-            file_location = json_content["file_location"].as_str().unwrap(); 
+            file_location = match json_content["file_location"].as_str() {
+                Some(file_location) => file_location,
+                None => return Err(field_error("file_location", "a string or null")),
+            };
+        }
+
+        let mut tags = BTreeSet::new();
+        if !json_content["tags"].is_array() {
+            return Err(field_error("tags", "a list"));
+        }
+        for tag in json_content["tags"].members() {
+            match tag.as_str() {
+                Some(tag) => tags.insert(String::from(tag)),
+                None => return Err(field_error("tags", "a list of strings")),
+            };
+        }
+
+        if !json_content["children"].is_null() && !json_content["children"].is_array() {
+            return Err(field_error("children", "a list"));
         }
 
-        let mut entry = EntryBuilder::new(id).set_text(text).set_is_category(is_category).set_full_file_location(String::from(file_location)).set_sort_type(sort_type).set_sort_descending(sort_descending).set_time_created(time_created).build();
+        let mut entry = EntryBuilder::new(id).set_text(text).set_is_category(is_category).set_full_file_location(String::from(file_location)).set_sort_type(sort_type).set_sort_descending(sort_descending).set_time_created(time_created).set_tags(tags).set_create_directories(false).build();
 
         for child in json_content["children"].members() {
-            let child_entry = Entry::build_entry_from_json(child); 
+            let child_path = format!("{}/{}", path, try_unwrap(child["text"].as_str(), "?"));
+            let child_entry = Entry::build_entry_from_json_at(child, child_path.as_str())?;
             entry.add_child(child_entry);
         } 
 
-        entry
+        Ok(entry)
     }
 
     pub fn get_as_json(&self) -> json::JsonValue {
@@ -366,41 +578,70 @@ impl Entry {
         for child in self.get_children().iter() {
                 json_children.push(child.get_as_json()); 
         }
-      
+
+        let mut json_entry = self.get_fields_as_json();
+        json_entry["children"] = json_children.into();
+
+        json_entry
+    }
+
+    pub fn get_fields_as_json(&self) -> json::JsonValue {
         if self.file_location.as_str() == "" {
             object! {
                 "id" => self.id,
                 "is_category" => self.is_category,
                 "text" => self.text.as_str(),
-                "children" => json_children,
                 "file_location" => json::Null,
                 "time_created" => self.time_created.timestamp(),
                 "sort_type" => get_int_from_sort_type(self.sort_type.clone()),
                 "sort_descending" => self.sort_descending,
+                "tags" => self.get_tags_as_json(),
             }         
         } else {
             object! {
                 "id" => self.id,
                 "is_category" => self.is_category,
                 "text" => self.text.as_str(),
-                "children" => json_children,
                 "file_location" => self.file_location.as_str(),
                 "time_created" => self.time_created.timestamp(),
                 "sort_type" => get_int_from_sort_type(self.sort_type.clone()),
                 "sort_descending" => self.sort_descending,
+                "tags" => self.get_tags_as_json(),
             } 
         }
     }
 
+    fn get_tags_as_json(&self) -> json::JsonValue {
+        json::JsonValue::Array(self.tags.iter().map(|tag| tag.as_str().into()).collect())
+    }
+
     pub fn sort_children(&mut self) {
-        match self.sort_type {
-            SortType::NAME => self.children.sort_by(|a, b| a.text.to_lowercase().cmp(&b.text.to_lowercase())), 
-            SortType::FILE => self.children.sort_by(|a, b| a.file_location.to_lowercase().cmp(&b.file_location.to_lowercase())),
-            SortType::TIME => self.children.sort_by(|a, b| a.time_created.cmp(&b.time_created)),
-        }
+        let mut children = mem::replace(&mut self.children, Vec::new());
+        children.sort_by(|a, b| self.compare(a, b));
+
+        self.children = children;
+    }
+
+    //Children in the order the category's sort settings ask for, without relying on the stored order
+    pub fn get_sorted_children(&self) -> Vec<&Entry> {
+        let mut children = self.get_children();
+        children.sort_by(|a, b| self.compare(a, b));
+
+        children
+    }
+
+    //Where two children go relative to each other under this category's sort settings
+    fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        let ordering = match self.sort_type {
+            SortType::NAME => a.text.to_lowercase().cmp(&b.text.to_lowercase()),
+            SortType::FILE => a.file_location.to_lowercase().cmp(&b.file_location.to_lowercase()),
+            SortType::TIME => a.time_created.cmp(&b.time_created),
+        };
 
         if self.sort_descending {
-            self.children.reverse(); 
+            ordering.reverse()
+        } else {
+            ordering
         }
     }
 
@@ -420,14 +661,6 @@ impl Entry {
         self.sort_descending 
     }
 
-    pub fn activate(&mut self) {
-        if !self.is_category && self.file_location.as_str() != "" {
-            self.open_file();
-        } else {
-            self.toggle_open() 
-        } 
-    }
-    
     pub fn delete(&mut self) {
         self.is_deleted = true; 
     }
@@ -458,20 +691,25 @@ fn get_int_from_sort_type(val: SortType) -> i8 {
     }
 }
 
-fn get_file_name(file_path: &String) -> String {
-    let index = &file_path[0..file_path.len() - 1].rfind('/').unwrap();
+//Categories keep their trailing slash
+pub fn get_file_name(file_path: &String) -> String {
+    let name = file_path.trim_end_matches('/').rsplit('/').next().unwrap_or("");
 
-    String::from(&file_path[index + 1..file_path.len()])
+    if file_path.ends_with('/') {
+        format!("{}/", name)
+    } else {
+        String::from(name)
+    }
 }
 
 pub fn build_full_file_path(mut file_path: String, nm: &note_manager::NoteManager, category_id: u32, is_category: bool) -> String {
-    if is_category && &file_path[file_path.len() - 1..file_path.len()] != "/" {
+    if is_category && !file_path.ends_with('/') {
         file_path = format!("{}/", file_path);
-    } else if !is_category && &file_path[file_path.len() - 1..file_path.len()] == "/" {
-        file_path = String::from(&file_path[0..file_path.len() - 1]);     
+    } else if !is_category && file_path.ends_with('/') {
+        file_path.pop();
     }
 
-    if &file_path[0..1] == "/" {
+    if file_path.starts_with('/') {
         file_path 
     } else {
         let mut new_file_path = nm.root.get_child_by_id(category_id).unwrap().file_location.clone();
@@ -501,6 +739,10 @@ impl Clone for Entry {
             time_created: self.time_created.clone(),
             sort_type: self.sort_type.clone(),
             sort_descending: self.sort_descending,
+            tags: self.tags.clone(),
+            filtered_out: Cell::new(self.filtered_out.get()),
+            filter_expanded: Cell::new(self.filter_expanded.get()),
+            missing: Cell::new(self.missing.get()),
         }
     }    
 }
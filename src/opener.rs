@@ -0,0 +1,236 @@
+extern crate glob;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::result::Result;
+
+const DEFAULT_EDITOR: &str = "vi";
+
+enum OpenerPattern {
+    EXTENSION(String),
+    GLOB(glob::Pattern),
+}
+
+struct OpenerRule {
+    pattern: OpenerPattern,
+    command: String,
+}
+
+//Picks the program a note is opened with, the first matching rule wins and everything else goes to the editor
+pub struct Opener {
+    rules: Vec<OpenerRule>,
+    editor: Option<String>,
+}
+
+//$VISUAL, then $EDITOR, then vi
+pub fn default_editor() -> String {
+    for variable in ["VISUAL", "EDITOR"].iter() {
+        if let Ok(editor) = env::var(variable) {
+            if !editor.trim().is_empty() {
+                return editor;
+            }
+        }
+    }
+
+    String::from(DEFAULT_EDITOR)
+}
+
+impl Opener {
+    pub fn new() -> Opener {
+        Opener {
+            rules: Vec::new(),
+            editor: None,
+        }
+    }
+
+    //The editor from the config comes before $VISUAL and $EDITOR
+    pub fn set_editor(&mut self, editor: Option<String>) {
+        self.editor = editor.filter(|editor| !editor.trim().is_empty());
+    }
+
+    //A pattern starting with a dot matches that extension, anything else is a glob on the file name or full path
+    pub fn add_rule(&mut self, pattern: &str, command: &str) -> Result<(), String> {
+        if command.is_empty() {
+            return Err(format!("no command given for {}", pattern));
+        }
+        split_command(command)?;
+
+        let pattern = if pattern.starts_with(".") && !pattern.contains(|c| c == '*' || c == '?' || c == '[' || c == '/') {
+            OpenerPattern::EXTENSION(pattern[1..].to_lowercase())
+        } else {
+            match glob::Pattern::new(pattern) {
+                Ok(pattern) => OpenerPattern::GLOB(pattern),
+                Err(e) => return Err(format!("invalid pattern {}: {}", pattern, e)),
+            }
+        };
+
+        self.rules.push(OpenerRule {
+            pattern: pattern,
+            command: String::from(command),
+        });
+
+        Ok(())
+    }
+
+    //The command template used for a file, {path} and {line} get filled in when it is run
+    pub fn command_for(&self, path: &str, line: Option<usize>) -> String {
+        let file_name = Path::new(path).file_name().map_or(String::new(), |file_name| file_name.to_string_lossy().into_owned());
+        let extension = Path::new(path).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+
+        for rule in self.rules.iter() {
+            let matches = match rule.pattern {
+                OpenerPattern::EXTENSION(ref rule_extension) => extension.as_ref() == Some(rule_extension),
+                OpenerPattern::GLOB(ref pattern) => pattern.matches(file_name.as_str()) || pattern.matches(path),
+            };
+
+            if matches {
+                return rule.command.clone();
+            }
+        }
+
+        let editor = self.editor.clone().unwrap_or_else(default_editor);
+
+        match line {
+            Some(_) => format!("{} +{{line}} {{path}}", editor),
+            None => format!("{} {{path}}", editor),
+        }
+    }
+
+    pub fn open(&self, path: &str, line: Option<usize>) -> Result<(), String> {
+        run_command(self.command_for(path, line).as_str(), path, line)
+    }
+}
+
+//Runs a command template without a shell, so paths with spaces stay one argument
+pub fn run_command(template: &str, path: &str, line: Option<usize>) -> Result<(), String> {
+    let args = build_args(template, path, line)?;
+
+    match Command::new(args[0].as_str()).args(&args[1..]).status() {
+        Ok(ref status) if status.success() => Ok(()),
+        Ok(status) => match status.code() {
+            Some(code) => Err(format!("{} exited with status {}", args[0], code)),
+            None => Err(format!("{} was killed by a signal", args[0])),
+        },
+        Err(e) => Err(format!("Could not launch {}: {}", args[0], e)),
+    }
+}
+
+//The path goes last when the template doesn't say where it goes
+fn build_args(template: &str, path: &str, line: Option<usize>) -> Result<Vec<String>, String> {
+    let mut args = split_command(template)?;
+
+    if !template.contains("{path}") {
+        args.push(String::from("{path}"));
+    }
+
+    let line = line.unwrap_or(1).to_string();
+
+    Ok(args.iter().map(|arg| fill_placeholders(arg, path, line.as_str())).collect())
+}
+
+//Goes over the argument once, so a path that has {line} in it is left alone
+fn fill_placeholders(arg: &str, path: &str, line: &str) -> String {
+    let mut filled = String::new();
+    let mut rest = arg;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("{path}") {
+            filled.push_str(path);
+            rest = &rest["{path}".len()..];
+        } else if rest.starts_with("{line}") {
+            filled.push_str(line);
+            rest = &rest["{line}".len()..];
+        } else {
+            filled.push('{');
+            rest = &rest[1..];
+        }
+    }
+    filled.push_str(rest);
+
+    filled
+}
+
+//Splits on whitespace, single and double quotes group words together
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+
+    for c in command.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => arg.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            },
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(arg.clone());
+                    arg.clear();
+                    in_arg = false;
+                }
+            },
+            None => {
+                arg.push(c);
+                in_arg = true;
+            },
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("unterminated quote in {}", command));
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    if args.is_empty() {
+        return Err(String::from("the command is empty"));
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(template: &str, path: &str, line: Option<usize>) -> Vec<String> {
+        build_args(template, path, line).unwrap()
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(split_command("code -g \"{path}:{line}\"").unwrap(), vec!["code", "-g", "{path}:{line}"]);
+        assert_eq!(split_command("'my editor'  --wait").unwrap(), vec!["my editor", "--wait"]);
+        assert_eq!(split_command("a \"b c\"d 'e\"f'").unwrap(), vec!["a", "b cd", "e\"f"]);
+        assert_eq!(split_command("a ''").unwrap(), vec!["a", ""]);
+    }
+
+    #[test]
+    fn bad_commands_are_errors() {
+        assert!(split_command("").is_err());
+        assert!(split_command("   ").is_err());
+        assert!(split_command("vim \"{path}").is_err());
+        assert!(split_command("vim '{path}").is_err());
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(args("vim +{line} {path}", "/tmp/a b.md", Some(12)), vec!["vim", "+12", "/tmp/a b.md"]);
+        assert_eq!(args("code -g {path}:{line}", "/tmp/a.md", None), vec!["code", "-g", "/tmp/a.md:1"]);
+        assert_eq!(args("zathura", "/tmp/a.pdf", Some(3)), vec!["zathura", "/tmp/a.pdf"]);
+        assert_eq!(args("echo {other} {", "/tmp/a.md", None), vec!["echo", "{other}", "{", "/tmp/a.md"]);
+    }
+
+    #[test]
+    fn paths_are_not_filled_in_again() {
+        assert_eq!(args("vim +{line} {path}", "/tmp/{line}/{path}.md", Some(5)), vec!["vim", "+5", "/tmp/{line}/{path}.md"]);
+        assert_eq!(args("less", "/tmp/{line}.md", Some(5)), vec!["less", "/tmp/{line}.md"]);
+    }
+}
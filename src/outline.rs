@@ -0,0 +1,447 @@
+use chrono::prelude::*;
+use std::path::Path;
+use std::result::Result;
+use notes;
+use sync;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutlineFormat {
+    OPML,
+    MARKDOWN,
+}
+
+pub struct OutlineItem {
+    pub text: String,
+    pub link: Option<String>,
+    pub created: Option<i64>,
+    pub children: Vec<OutlineItem>,
+}
+
+impl OutlineItem {
+    fn new(text: &str) -> OutlineItem {
+        OutlineItem {
+            text: String::from(text.trim()),
+            link: None,
+            created: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+pub fn get_format_from_str(format: &str) -> Result<OutlineFormat, String> {
+    match format.to_lowercase().as_str() {
+        "opml" | "xml" => Ok(OutlineFormat::OPML),
+        "markdown" | "md" => Ok(OutlineFormat::MARKDOWN),
+        _ => Err(format!("Unknown outline format {}, use opml or markdown", format)),
+    }
+}
+
+pub fn format_for_file(file_name: &str) -> OutlineFormat {
+    match Path::new(file_name).extension().and_then(|extension| extension.to_str()) {
+        Some("opml") | Some("xml") => OutlineFormat::OPML,
+        _ => OutlineFormat::MARKDOWN,
+    }
+}
+
+pub fn parse(contents: &str, format: OutlineFormat) -> Result<Vec<OutlineItem>, String> {
+    match format {
+        OutlineFormat::OPML => parse_opml(contents),
+        OutlineFormat::MARKDOWN => Ok(parse_markdown(contents)),
+    }
+}
+
+pub fn category_location(category: &notes::Entry) -> String {
+    let mut location = category.file_location.clone();
+    if !location.ends_with("/") {
+        location.push('/');
+    }
+
+    location
+}
+
+//Items with children become categories with a directory under parent_location, the rest become notes
+//Only called for a real import, the directories made up for categories without a link get created here
+pub fn build_entries(items: &Vec<OutlineItem>, parent_location: &str, next_id: &mut u32) -> Vec<notes::Entry> {
+    let mut entries = Vec::new();
+
+    for item in items.iter() {
+        let is_category = !item.children.is_empty();
+
+        let link = item.link.as_ref().map(|link| match link.starts_with("file://") {
+            true => String::from(&link["file://".len()..]),
+            false => link.clone(),
+        });
+
+        //Categories need a directory, so links to anything else are dropped for them
+        let (mut file_location, create_directories) = match link {
+            Some(link) if !is_category || sync::is_local_path(link.as_str()) => (link, false),
+            _ if is_category => (format!("{}{}/", parent_location, item.text.replace("/", "-")), true),
+            _ => (String::new(), false),
+        };
+
+        if is_category && !file_location.ends_with("/") {
+            file_location.push('/');
+        }
+
+        let mut builder = notes::EntryBuilder::new(*next_id).set_text(item.text.as_str()).set_is_category(is_category).set_full_file_location(file_location.clone()).set_create_directories(create_directories);
+        *next_id += 1;
+
+        if let Some(created) = item.created {
+            builder = builder.set_time_created(created);
+        }
+
+        let mut entry = builder.build();
+
+        for child in build_entries(&item.children, file_location.as_str(), next_id) {
+            entry.add_child(child);
+        }
+
+        entries.push(entry);
+    }
+
+    entries
+}
+
+pub fn parse_opml(contents: &str) -> Result<Vec<OutlineItem>, String> {
+    let mut stack: Vec<OutlineItem> = vec![OutlineItem::new("")];
+    let mut rest = contents;
+
+    while let Some(start) = rest.find('<') {
+        //Comments can hold quotes that aren't attribute values
+        if rest[start..].starts_with("<!--") {
+            match rest[start..].find("-->") {
+                Some(end) => rest = &rest[start + end + 3..],
+                None => return Err(String::from("unterminated comment")),
+            }
+            continue;
+        }
+
+        let end = match find_tag_end(&rest[start..]) {
+            Some(end) => start + end,
+            None => return Err(String::from("unterminated tag")),
+        };
+
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with("/outline") {
+            if stack.len() < 2 {
+                return Err(String::from("</outline> without a matching <outline>"));
+            }
+
+            let item = stack.pop().unwrap();
+            stack.last_mut().unwrap().children.push(item);
+        } else if tag.starts_with("outline") {
+            let attributes = parse_attributes(&tag["outline".len()..].trim_end_matches('/'));
+            let get = |name: &str| attributes.iter().find(|&&(ref key, _)| key.as_str() == name).map(|&(_, ref value)| value.clone());
+
+            let mut item = OutlineItem::new(get("text").or(get("title")).unwrap_or(String::new()).as_str());
+            item.link = get("url").or(get("htmlUrl")).or(get("xmlUrl")).or(get("path"));
+            item.created = get("created").and_then(|created| DateTime::parse_from_rfc2822(created.as_str()).ok()).map(|created| created.timestamp());
+
+            if tag.ends_with("/") {
+                stack.last_mut().unwrap().children.push(item);
+            } else {
+                stack.push(item);
+            }
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err(String::from("<outline> without a matching </outline>"));
+    }
+
+    Ok(stack.pop().unwrap().children)
+}
+
+//A > inside a quoted attribute value doesn't end the tag
+fn find_tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+fn parse_attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = text.trim();
+
+    while let Some(equals) = rest.find('=') {
+        let name = rest[..equals].trim();
+        let value_start = rest[equals + 1..].trim_start();
+
+        let quote = match value_start.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+
+        let value_end = match value_start[1..].find(quote) {
+            Some(value_end) => value_end + 1,
+            None => break,
+        };
+
+        attributes.push((String::from(name), unescape_xml(&value_start[1..value_end])));
+        rest = value_start[value_end + 1..].trim_start();
+    }
+
+    attributes
+}
+
+fn unescape_xml(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+
+        let end = match rest[start..].find(';') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let entity = &rest[start + 1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32),
+            _ if entity.starts_with("#") => entity[1..].parse::<u32>().ok().and_then(::std::char::from_u32),
+            _ => None,
+        };
+
+        match decoded {
+            Some(c) => output.push(c),
+            None => output.push_str(&rest[start..end + 1]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    output.push_str(rest);
+
+    output
+}
+
+//Headings and list items both nest, list items sit under the closest heading above them
+pub fn parse_markdown(contents: &str) -> Vec<OutlineItem> {
+    let mut root = OutlineItem::new("");
+    //Nesting level of every open item, deepest last
+    let mut levels: Vec<usize> = Vec::new();
+    let mut heading_level = 0;
+
+    for line in contents.lines() {
+        let expanded = line.replace("\t", "    ");
+        let indent = expanded.len() - expanded.trim_start().len();
+        let trimmed = expanded.trim();
+
+        let (level, text) = if trimmed.starts_with("#") {
+            let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+            heading_level = hashes * 1000;
+
+            (heading_level, trimmed[hashes..].trim())
+        } else if let Some(text) = list_item_text(trimmed) {
+            (heading_level + 1 + indent, text)
+        } else {
+            continue;
+        };
+
+        if text.is_empty() {
+            continue;
+        }
+
+        //A heading also closes every list item above it
+        let is_heading = level % 1000 == 0;
+        while levels.last().map_or(false, |&last| last >= level || (is_heading && last % 1000 != 0)) {
+            levels.pop();
+        }
+
+        let mut parent = &mut root;
+        for _ in 0..levels.len() {
+            parent = parent.children.last_mut().unwrap();
+        }
+
+        parent.children.push(parse_markdown_item(text));
+        levels.push(level);
+    }
+
+    root.children
+}
+
+fn list_item_text(line: &str) -> Option<&str> {
+    if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+        return Some(line[2..].trim());
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") ")) {
+        return Some(line[digits + 2..].trim());
+    }
+
+    None
+}
+
+//Handles [text](link) and **text**, a bare path or url is used as the link
+fn parse_markdown_item(text: &str) -> OutlineItem {
+    let mut text = text.trim();
+
+    if text.starts_with("[ ] ") || text.starts_with("[x] ") || text.starts_with("[X] ") {
+        text = text[4..].trim();
+    }
+
+    if text.starts_with("[") {
+        if let Some(close) = text.find("](") {
+            if text.ends_with(")") {
+                let mut item = OutlineItem::new(unescape_markdown(&text[1..close]).as_str());
+                item.link = Some(unescape_link(&text[close + 2..text.len() - 1]));
+
+                return item;
+            }
+        }
+    }
+
+    let text = text.replace("**", "");
+    let mut item = OutlineItem::new(unescape_markdown(text.as_str()).as_str());
+
+    if !text.contains(' ') && (text.starts_with("/") || text.contains("://")) {
+        item.link = Some(text.clone());
+    }
+
+    item
+}
+
+fn unescape_markdown(text: &str) -> String {
+    let mut output = String::new();
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if c == '\\' && !escaped {
+            escaped = true;
+        } else {
+            output.push(c);
+            escaped = false;
+        }
+    }
+
+    output
+}
+
+//Decodes %XX escapes, a % that doesn't start one is kept as it is
+fn unescape_link(link: &str) -> String {
+    let bytes = link.as_bytes();
+    let mut output = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let code = match bytes[i] {
+            b'%' => link.get(i + 1..i + 3).filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit())).and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match code {
+            Some(code) => {
+                output.push(code);
+                i += 3;
+            },
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+
+    String::from_utf8(output).unwrap_or(String::from(link))
+}
+
+pub fn count_items(items: &Vec<OutlineItem>) -> usize {
+    items.iter().map(|item| 1 + count_items(&item.children)).sum()
+}
+
+pub fn preview(items: &Vec<OutlineItem>) -> Vec<String> {
+    let mut lines = Vec::new();
+    preview_items(items, 0, &mut lines);
+
+    lines
+}
+
+fn preview_items(items: &Vec<OutlineItem>, depth: usize, lines: &mut Vec<String>) {
+    for item in items.iter() {
+        let link = match item.link {
+            Some(ref link) => format!(": {}", link),
+            None => String::new(),
+        };
+
+        lines.push(format!("{}{}{}", "  ".repeat(depth), item.text, link));
+        preview_items(&item.children, depth + 1, lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_opml() {
+        let contents = r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <head><title>Notes</title></head>
+  <body>
+    <outline text="Work">
+      <outline text="Plans">
+        <outline text="Q1 &amp; Q2" url="/home/me/q.md"/>
+      </outline>
+      <outline title="Ideas" />
+    </outline>
+    <outline text="Home"/>
+  </body>
+</opml>"#;
+
+        let items = parse_opml(contents).unwrap();
+        assert_eq!(preview(&items), vec!["Work", "  Plans", "    Q1 & Q2: /home/me/q.md", "  Ideas", "Home"]);
+        assert_eq!(count_items(&items), 5);
+    }
+
+    #[test]
+    fn opml_quotes_and_comments() {
+        let contents = r#"<body>
+  <!-- don't stop at the quote in here -->
+  <outline text="a > b" path='/tmp/it&apos;s "here"'>
+    <outline text='x>y'/>
+  </outline>
+</body>"#;
+
+        let items = parse_opml(contents).unwrap();
+        assert_eq!(preview(&items), vec!["a > b: /tmp/it's \"here\"", "  x>y"]);
+    }
+
+    #[test]
+    fn unbalanced_opml_is_an_error() {
+        assert!(parse_opml("<outline text=\"a\">").is_err());
+        assert!(parse_opml("</outline>").is_err());
+        assert!(parse_opml("<outline text=\"a\"").is_err());
+        assert!(parse_opml("<!-- <outline text=\"a\"/>").is_err());
+    }
+
+    #[test]
+    fn nested_markdown() {
+        let contents = "# Work\n- Plans\n  - [Q1](/home/me/q1.md)\n  1. **Bold**\n- /tmp/file.txt\n## Later\n* [ ] todo\n# Home\n";
+
+        let items = parse_markdown(contents);
+        assert_eq!(preview(&items), vec!["Work", "  Plans", "    Q1: /home/me/q1.md", "    Bold", "  /tmp/file.txt: /tmp/file.txt", "  Later", "    todo", "Home"]);
+    }
+
+    #[test]
+    fn markdown_link_escapes() {
+        let items = parse_markdown("- [Notes](file:///home/me/my%20notes%20%281%29.md)\n- [Odd](/tmp/100%/%zz)\n");
+        assert_eq!(preview(&items), vec!["Notes: file:///home/me/my notes (1).md", "Odd: /tmp/100%/%zz"]);
+    }
+}
@@ -0,0 +1,77 @@
+extern crate json;
+
+use chrono::prelude::*;
+
+pub const SCHEMA_VERSION: u32 = 2;
+
+//migrations[n] upgrades a file from schema version n to n + 1
+const MIGRATIONS: [fn(&mut json::JsonValue); 2] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+];
+
+pub fn migrate(contents_json: &mut json::JsonValue) -> Result<(), String> {
+    let mut version = if contents_json.has_key("schema_version") {
+        match contents_json["schema_version"].as_u32() {
+            Some(version) => version,
+            None => return Err(String::from("field `schema_version` is not a number")),
+        }
+    } else {
+        0
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(format!("schema version {} is newer than the supported version {}", version, SCHEMA_VERSION));
+    }
+
+    while version < SCHEMA_VERSION {
+        MIGRATIONS[version as usize](contents_json);
+        version += 1;
+        contents_json["schema_version"] = version.into();
+    }
+
+    Ok(())
+}
+
+//Files written before schema_version existed could be missing the sort and time fields
+fn migrate_v0_to_v1(contents_json: &mut json::JsonValue) {
+    fn migrate_entry(entry: &mut json::JsonValue, now: i64) {
+        if entry["sort_type"].is_null() {
+            entry["sort_type"] = 0.into();
+        }
+
+        if entry["sort_descending"].is_null() {
+            entry["sort_descending"] = false.into();
+        }
+
+        if entry["time_created"].is_null() {
+            entry["time_created"] = now.into();
+        }
+
+        if entry["is_category"].is_null() {
+            let is_category = entry["children"].len() > 0 || entry["file_location"].as_str().map_or(false, |location| location.ends_with("/"));
+            entry["is_category"] = is_category.into();
+        }
+
+        for child in entry["children"].members_mut() {
+            migrate_entry(child, now);
+        }
+    }
+
+    migrate_entry(&mut contents_json["root"], Local::now().timestamp());
+}
+
+//Version 2 added tags
+fn migrate_v1_to_v2(contents_json: &mut json::JsonValue) {
+    fn migrate_entry(entry: &mut json::JsonValue) {
+        if entry["tags"].is_null() {
+            entry["tags"] = json::JsonValue::new_array();
+        }
+
+        for child in entry["children"].members_mut() {
+            migrate_entry(child);
+        }
+    }
+
+    migrate_entry(&mut contents_json["root"]);
+}
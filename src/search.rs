@@ -0,0 +1,179 @@
+extern crate regex;
+
+use self::regex::{Regex, RegexBuilder};
+use std::result::Result;
+
+const SNIPPET_LENGTH: usize = 120;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CaseMode {
+    SMART,
+    SENSITIVE,
+    INSENSITIVE,
+}
+
+pub struct SearchResult {
+    pub id: u32,
+    pub file: String,
+    pub line: usize,
+    pub snippet: String,
+}
+
+pub struct Query {
+    regex: Regex,
+    literal: Option<String>,
+}
+
+impl Query {
+    pub fn new(pattern: &str, is_regex: bool, case: CaseMode) -> Result<Query, String> {
+        if pattern.is_empty() {
+            return Err(String::from("the search is empty"));
+        }
+
+        //Smart case only cares about case when the pattern has an upper case letter in it
+        let case_insensitive = match case {
+            CaseMode::SMART => !pattern.chars().any(|c| c.is_uppercase()),
+            CaseMode::SENSITIVE => false,
+            CaseMode::INSENSITIVE => true,
+        };
+
+        let source = match is_regex {
+            true => String::from(pattern),
+            false => regex::escape(pattern),
+        };
+
+        match RegexBuilder::new(source.as_str()).case_insensitive(case_insensitive).build() {
+            Ok(regex) => Ok(Query {
+                regex: regex,
+                literal: if is_regex { None } else { Some(String::from(pattern)) },
+            }),
+            //Regex errors span several lines, the last one says what is wrong
+            Err(e) => Err(format!("invalid regex: {}", e.to_string().lines().last().unwrap_or("").trim_start_matches("error: "))),
+        }
+    }
+
+    //A query wrapped in slashes is a regex, flags after the last slash are i for ignore case and c for match case
+    //Anything else after the last slash (like in /usr/bin/env) means it is just a path to look for
+    pub fn parse(input: &str) -> Result<Query, String> {
+        let input = input.trim();
+
+        if input.len() > 1 && input.starts_with("/") {
+            if let Some(end) = input.rfind('/').filter(|&end| end > 0) {
+                let mut case = Some(CaseMode::SMART);
+
+                for flag in input[end + 1..].chars() {
+                    case = match flag {
+                        'i' => case.map(|_| CaseMode::INSENSITIVE),
+                        'c' => case.map(|_| CaseMode::SENSITIVE),
+                        _ => None,
+                    };
+                }
+
+                if let Some(case) = case {
+                    return Query::new(&input[1..end], true, case);
+                }
+            }
+        }
+
+        Query::new(input, false, CaseMode::SMART)
+    }
+
+    pub fn get_literal(&self) -> Option<&str> {
+        self.literal.as_ref().map(|literal| literal.as_str())
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+pub fn search_line(id: u32, file: &str, line_number: usize, line: &str, query: &Query, results: &mut Vec<SearchResult>) {
+    if query.is_match(line) {
+        results.push(SearchResult {
+            id: id,
+            file: String::from(file),
+            line: line_number,
+            snippet: get_snippet(line, query),
+        });
+    }
+}
+
+//Long lines are cut down to the part around the match
+fn get_snippet(line: &str, query: &Query) -> String {
+    let line = line.trim();
+    let chars: Vec<char> = line.chars().collect();
+
+    if chars.len() <= SNIPPET_LENGTH {
+        return String::from(line);
+    }
+
+    let match_center = match query.regex.find(line) {
+        Some(found) => (line[..found.start()].chars().count() + line[..found.end()].chars().count()) / 2,
+        None => 0,
+    };
+
+    let start = match_center.saturating_sub(SNIPPET_LENGTH / 2).min(chars.len() - SNIPPET_LENGTH);
+    let end = start + SNIPPET_LENGTH;
+
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet = format!("...{}", snippet);
+    }
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(input: &str, text: &str) -> bool {
+        Query::parse(input).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn slashes_make_a_regex() {
+        assert!(matches("/fo+ bar/", "a foooo bar"));
+        assert!(!matches("/^bar/", "foo bar"));
+        assert!(Query::parse("/fo+/").unwrap().get_literal().is_none());
+    }
+
+    #[test]
+    fn regex_flags_set_the_case() {
+        assert!(matches("/Foo/i", "foo"));
+        assert!(!matches("/foo/c", "FOO"));
+        assert!(matches("/foo/", "FOO"));
+        assert!(!matches("/Foo/", "foo"));
+        assert!(!matches("/foo/ic", "FOO"));
+    }
+
+    #[test]
+    fn paths_are_literal() {
+        assert!(matches("/usr/bin/env", "#!/usr/bin/env sh"));
+        assert!(!matches("/usr/bin/env", "usr bin env"));
+        assert_eq!(Query::parse("/usr/bin/env").unwrap().get_literal(), Some("/usr/bin/env"));
+        assert_eq!(Query::parse("/a.c/x").unwrap().get_literal(), Some("/a.c/x"));
+        assert!(!matches("/a.c/x", "abc/x"));
+        assert_eq!(Query::parse("/").unwrap().get_literal(), Some("/"));
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(matches("hello", "HELLO there"));
+        assert!(matches("Hello", "Hello there"));
+        assert!(!matches("Hello", "hello there"));
+        assert!(matches("a.c", "A.C"));
+        assert!(!matches("a.c", "abc"));
+    }
+
+    #[test]
+    fn bad_queries_are_errors() {
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("   ").is_err());
+        assert!(Query::parse("//").is_err());
+        assert!(Query::parse("/(/").is_err());
+    }
+}
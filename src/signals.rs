@@ -0,0 +1,56 @@
+extern crate libc;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static PENDING_SIGNAL: AtomicUsize = AtomicUsize::new(0);
+static SUSPEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_signal(signal: libc::c_int) {
+    PENDING_SIGNAL.store(signal as usize, Ordering::SeqCst);
+}
+
+extern "C" fn handle_suspend(_signal: libc::c_int) {
+    SUSPEND_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+//Must be called after initscr so these handlers replace the ones curses installs
+pub fn init_signals() {
+    unsafe {
+        libc::signal(libc::SIGTERM, handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        libc::signal(libc::SIGHUP, handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        libc::signal(libc::SIGTSTP, handle_suspend as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+//Ctrl-Z only sets a flag, the main loop leaves curses mode before actually stopping
+pub fn take_suspend_request() -> bool {
+    SUSPEND_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+//Stops the process until it gets continued from the shell
+pub fn stop_process() {
+    allow_stop(true);
+
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+
+    allow_stop(false);
+}
+
+//While another program has the terminal Ctrl-Z has to stop us together with it
+pub fn allow_stop(allow: bool) {
+    unsafe {
+        if allow {
+            libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        } else {
+            libc::signal(libc::SIGTSTP, handle_suspend as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+    }
+}
+
+pub fn exit_requested() -> bool {
+    let signal = PENDING_SIGNAL.load(Ordering::SeqCst) as libc::c_int;
+
+    signal == libc::SIGTERM || signal == libc::SIGHUP
+}
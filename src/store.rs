@@ -0,0 +1,353 @@
+extern crate json;
+extern crate rusqlite;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::result::Result;
+use self::json::object;
+use self::rusqlite::{Connection, OptionalExtension};
+use notes;
+use schema;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StoreKind {
+    JSON,
+    SQLITE,
+}
+
+pub trait NoteStore {
+    //Ok(None) means there is no notebook yet
+    fn load(&mut self) -> Result<Option<(notes::Entry, u32)>, String>;
+    fn save(&mut self, root: &notes::Entry, next_id: u32) -> io::Result<()>;
+
+    //Lets a store that writes entries one at a time skip the ones that did not change
+    fn entry_changed(&mut self, _id: u32) {}
+    fn notebook_changed(&mut self) {}
+}
+
+pub struct JsonStore {
+    file_name: String,
+}
+
+//Each entry is a row holding its fields as JSON, so rows are versioned and migrated the same way as the JSON file
+pub struct SqliteStore {
+    file_name: String,
+    connection: Option<Connection>,
+    saved_places: HashMap<u32, EntryPlace>,
+    changed_ids: HashSet<u32>,
+    all_changed: bool,
+}
+
+struct EntryRow {
+    parent_id: Option<u32>,
+    position: u32,
+    data: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct EntryPlace {
+    parent_id: Option<u32>,
+    position: u32,
+}
+
+pub fn kind_for_file(file_name: &str) -> StoreKind {
+    match Path::new(file_name).extension().and_then(|extension| extension.to_str()) {
+        Some("db") | Some("sqlite") | Some("sqlite3") => StoreKind::SQLITE,
+        _ => StoreKind::JSON,
+    }
+}
+
+pub fn open_store(file_name: &str, kind: StoreKind) -> Box<NoteStore> {
+    match kind {
+        StoreKind::JSON => Box::new(JsonStore::new(file_name)),
+        StoreKind::SQLITE => Box::new(SqliteStore::new(file_name)),
+    }
+}
+
+pub fn backup_file_name(file_name: &str) -> String {
+    format!("{}.bak", file_name)
+}
+
+impl JsonStore {
+    pub fn new(file_name: &str) -> JsonStore {
+        JsonStore {
+            file_name: String::from(file_name),
+        }
+    }
+}
+
+impl NoteStore for JsonStore {
+    fn load(&mut self) -> Result<Option<(notes::Entry, u32)>, String> {
+        //Only a missing file means there is no notebook yet, anything else has to keep the file from being saved over
+        let mut file = match File::open(self.file_name.as_str()) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut contents = String::new();
+        if let Err(e) = file.read_to_string(&mut contents) {
+            return Err(e.to_string());
+        }
+
+        let contents_json = match json::parse(contents.as_str()) {
+            Ok(contents_json) => contents_json,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        build_notebook(contents_json).map(Some)
+    }
+
+    fn save(&mut self, root: &notes::Entry, next_id: u32) -> io::Result<()> {
+        let json_output = object! {
+            "schema_version" => schema::SCHEMA_VERSION,
+            "next_id" => next_id,
+            "root" => root.get_as_json(),
+        };
+
+        let file_name = self.file_name.as_str();
+        let file_contents = json::stringify_pretty(json_output, 2);
+        let temp_file_name = format!("{}.tmp", file_name);
+
+        {
+            let mut temp_file = File::create(temp_file_name.as_str())?;
+
+            temp_file.write_all(file_contents.as_bytes())?;
+            temp_file.sync_all()?;
+        }
+
+        if Path::new(file_name).exists() {
+            fs::copy(file_name, backup_file_name(file_name))?;
+        }
+
+        fs::rename(temp_file_name.as_str(), file_name)?;
+
+        //Make sure the rename itself reaches the disk
+        let directory = match Path::new(file_name).parent() {
+            Some(parent) if parent.as_os_str().len() > 0 => parent.to_path_buf(),
+            _ => env::current_dir()?,
+        };
+        File::open(directory)?.sync_all()?;
+
+        Ok(())
+    }
+}
+
+impl SqliteStore {
+    pub fn new(file_name: &str) -> SqliteStore {
+        SqliteStore {
+            file_name: String::from(file_name),
+            connection: None,
+            saved_places: HashMap::new(),
+            changed_ids: HashSet::new(),
+            all_changed: false,
+        }
+    }
+
+    fn connect(&mut self) -> rusqlite::Result<&mut Connection> {
+        if self.connection.is_none() {
+            let connection = Connection::open(self.file_name.as_str())?;
+
+            connection.execute_batch("
+                CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL);
+                CREATE TABLE IF NOT EXISTS entries (
+                    id INTEGER PRIMARY KEY,
+                    parent_id INTEGER,
+                    position INTEGER NOT NULL,
+                    data TEXT NOT NULL
+                );
+            ")?;
+
+            self.connection = Some(connection);
+        }
+
+        Ok(self.connection.as_mut().unwrap())
+    }
+
+    fn read_rows(&mut self) -> rusqlite::Result<(Option<u32>, Option<u32>, Vec<(u32, EntryRow)>)> {
+        let connection = self.connect()?;
+
+        let schema_version = connection.query_row("SELECT value FROM meta WHERE key = 'schema_version'", [], |row| row.get(0)).optional()?;
+        let next_id = connection.query_row("SELECT value FROM meta WHERE key = 'next_id'", [], |row| row.get(0)).optional()?;
+
+        let mut statement = connection.prepare("SELECT id, parent_id, position, data FROM entries ORDER BY position")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get(0)?, EntryRow {
+                parent_id: row.get(1)?,
+                position: row.get(2)?,
+                data: row.get(3)?,
+            }))
+        })?.collect::<rusqlite::Result<Vec<(u32, EntryRow)>>>()?;
+
+        Ok((schema_version, next_id, rows))
+    }
+}
+
+impl NoteStore for SqliteStore {
+    fn load(&mut self) -> Result<Option<(notes::Entry, u32)>, String> {
+        if !Path::new(self.file_name.as_str()).exists() {
+            return Ok(None);
+        }
+
+        let (schema_version, next_id, rows) = match self.read_rows() {
+            Ok(rows) => rows,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        //Rebuild the nested layout of the JSON file so it goes through the same migrations and checks
+        let mut entries: HashMap<u32, json::JsonValue> = HashMap::new();
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut root_id = None;
+
+        for &(id, ref row) in rows.iter() {
+            match json::parse(row.data.as_str()) {
+                Ok(data) => entries.insert(id, data),
+                Err(e) => return Err(format!("entry {}: {}", id, e)),
+            };
+
+            match row.parent_id {
+                Some(parent_id) => children.entry(parent_id).or_insert_with(Vec::new).push(id),
+                None => root_id = Some(id),
+            }
+        }
+
+        let root_id = match root_id {
+            Some(root_id) => root_id,
+            None => return Err(String::from("no root entry")),
+        };
+
+        let mut contents_json = object! {
+            "next_id" => next_id,
+            "root" => nest_rows(root_id, &mut entries, &children),
+        };
+
+        if let Some(schema_version) = schema_version {
+            contents_json["schema_version"] = schema_version.into();
+        }
+
+        let notebook = build_notebook(contents_json)?;
+        self.saved_places = rows.iter().map(|&(id, ref row)| (id, EntryPlace { parent_id: row.parent_id, position: row.position })).collect();
+        self.changed_ids.clear();
+        self.all_changed = false;
+
+        Ok(Some(notebook))
+    }
+
+    fn save(&mut self, root: &notes::Entry, next_id: u32) -> io::Result<()> {
+        let mut places = HashMap::new();
+        let mut rows = Vec::new();
+
+        {
+            //Only entries that were edited or moved since the last save are written
+            let saved_places = &self.saved_places;
+            let changed_ids = &self.changed_ids;
+            let all_changed = self.all_changed;
+            let is_changed = |id: u32, place: &EntryPlace| all_changed || changed_ids.contains(&id) || saved_places.get(&id) != Some(place);
+
+            collect_rows(root, None, 0, &is_changed, &mut places, &mut rows);
+        }
+
+        self.connect().map_err(to_io_error)?;
+
+        let result = {
+            let connection = self.connection.as_mut().unwrap();
+            let saved_places = &self.saved_places;
+
+            (|| -> rusqlite::Result<()> {
+                let transaction = connection.transaction()?;
+
+                for &(id, ref row) in rows.iter() {
+                    transaction.execute("INSERT OR REPLACE INTO entries (id, parent_id, position, data) VALUES (?1, ?2, ?3, ?4)", (id, row.parent_id, row.position, row.data.as_str()))?;
+                }
+
+                for id in saved_places.keys() {
+                    if !places.contains_key(id) {
+                        transaction.execute("DELETE FROM entries WHERE id = ?1", [id])?;
+                    }
+                }
+
+                transaction.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('next_id', ?1)", [next_id])?;
+                transaction.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?1)", [schema::SCHEMA_VERSION])?;
+
+                transaction.commit()
+            })()
+        };
+
+        result.map_err(to_io_error)?;
+        self.saved_places = places;
+        self.changed_ids.clear();
+        self.all_changed = false;
+
+        Ok(())
+    }
+
+    fn entry_changed(&mut self, id: u32) {
+        self.changed_ids.insert(id);
+    }
+
+    fn notebook_changed(&mut self) {
+        self.all_changed = true;
+    }
+}
+
+fn build_notebook(mut contents_json: json::JsonValue) -> Result<(notes::Entry, u32), String> {
+    schema::migrate(&mut contents_json)?;
+
+    let next_id = match contents_json["next_id"].as_u32() {
+        Some(next_id) => next_id,
+        None => return Err(String::from("field `next_id` is missing or not a number")),
+    };
+
+    let root = notes::Entry::build_entry_from_json(&contents_json["root"])?;
+
+    Ok((root, next_id))
+}
+
+fn nest_rows(id: u32, entries: &mut HashMap<u32, json::JsonValue>, children: &HashMap<u32, Vec<u32>>) -> json::JsonValue {
+    let mut entry = entries.remove(&id).unwrap_or(json::JsonValue::new_object());
+    let mut json_children = json::JsonValue::new_array();
+
+    if let Some(child_ids) = children.get(&id) {
+        for child_id in child_ids {
+            let _ = json_children.push(nest_rows(*child_id, entries, children));
+        }
+    }
+
+    entry["children"] = json_children;
+
+    entry
+}
+
+fn collect_rows(entry: &notes::Entry, parent_id: Option<u32>, position: u32, is_changed: &Fn(u32, &EntryPlace) -> bool, places: &mut HashMap<u32, EntryPlace>, rows: &mut Vec<(u32, EntryRow)>) {
+    let place = EntryPlace {
+        parent_id: parent_id,
+        position: position,
+    };
+
+    if is_changed(entry.get_id(), &place) {
+        rows.push((entry.get_id(), EntryRow {
+            parent_id: parent_id,
+            position: position,
+            data: entry.get_fields_as_json().dump(),
+        }));
+    }
+
+    places.insert(entry.get_id(), place);
+
+    for (i, child) in entry.get_children().iter().enumerate() {
+        collect_rows(child, Some(entry.get_id()), i as u32, is_changed, places, rows);
+    }
+}
+
+fn to_io_error(e: rusqlite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
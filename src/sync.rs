@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use notes;
+
+pub struct SyncReport {
+    //Ids of entries whose file or directory no longer exists
+    pub missing: Vec<u32>,
+    //Category id and path of files found in a category directory that no entry points at
+    pub untracked: Vec<(u32, String)>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.untracked.is_empty()
+    }
+}
+
+//Only local paths are checked, anything else (urls for example) is left alone
+pub fn is_local_path(file_location: &str) -> bool {
+    file_location.starts_with("/")
+}
+
+pub fn is_missing(entry: &notes::Entry) -> bool {
+    is_local_path(entry.file_location.as_str()) && !Path::new(entry.file_location.as_str()).exists()
+}
+
+//Paths starting with one of ignored_prefixes (the notebook file and its backups for example) are never reported as untracked
+pub fn scan(root: &notes::Entry, ignored_prefixes: &Vec<String>) -> SyncReport {
+    let mut tracked = HashSet::new();
+    collect_tracked(root, &mut tracked);
+
+    let mut report = SyncReport {
+        missing: Vec::new(),
+        untracked: Vec::new(),
+    };
+
+    for child in root.get_children() {
+        scan_entry(child, &tracked, ignored_prefixes, &mut report);
+    }
+
+    report
+}
+
+fn collect_tracked(entry: &notes::Entry, tracked: &mut HashSet<String>) {
+    tracked.insert(normalize(entry.file_location.as_str()));
+
+    for child in entry.get_children() {
+        collect_tracked(child, tracked);
+    }
+}
+
+fn scan_entry(entry: &notes::Entry, tracked: &HashSet<String>, ignored_prefixes: &Vec<String>, report: &mut SyncReport) {
+    if is_missing(entry) {
+        report.missing.push(entry.get_id());
+        return;
+    }
+
+    if entry.can_add_child() && is_local_path(entry.file_location.as_str()) {
+        if let Ok(dir_entries) = fs::read_dir(entry.file_location.as_str()) {
+            let mut untracked = Vec::new();
+
+            for dir_entry in dir_entries {
+                if let Ok(dir_entry) = dir_entry {
+                    let name = dir_entry.file_name().to_string_lossy().into_owned();
+                    let path = dir_entry.path().to_string_lossy().into_owned();
+
+                    if name.starts_with(".") || tracked.contains(&normalize(path.as_str())) || ignored_prefixes.iter().any(|prefix| path.starts_with(prefix.as_str())) {
+                        continue;
+                    }
+
+                    untracked.push(path);
+                }
+            }
+
+            untracked.sort();
+            for path in untracked {
+                report.untracked.push((entry.get_id(), path));
+            }
+        }
+    }
+
+    for child in entry.get_children() {
+        scan_entry(child, tracked, ignored_prefixes, report);
+    }
+}
+
+fn normalize(file_location: &str) -> String {
+    String::from(file_location.trim_end_matches('/'))
+}
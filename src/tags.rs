@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+use std::result::Result;
+
+const SPECIAL_CHARACTERS: [char; 5] = ['(', ')', '!', '&', '|'];
+
+enum Expression {
+    TAG(String),
+    NOT(Box<Expression>),
+    AND(Box<Expression>, Box<Expression>),
+    OR(Box<Expression>, Box<Expression>),
+}
+
+pub struct TagFilter {
+    text: String,
+    expression: Expression,
+}
+
+#[derive(PartialEq)]
+enum Token {
+    TAG(String),
+    NOT,
+    AND,
+    OR,
+    OPEN,
+    CLOSE,
+}
+
+impl TagFilter {
+    //Tags can be combined with AND, OR, NOT (or &, |, !) and parentheses, tags next to each other are ANDed
+    pub fn parse(text: &str) -> Result<TagFilter, String> {
+        let tokens = tokenize(text);
+        if tokens.is_empty() {
+            return Err(String::from("the filter is empty"));
+        }
+
+        let mut position = 0;
+        let expression = parse_or(&tokens, &mut position)?;
+
+        if position < tokens.len() {
+            return Err(match tokens[position] {
+                Token::CLOSE => String::from("unmatched )"),
+                _ => String::from("unexpected input after the filter"),
+            });
+        }
+
+        Ok(TagFilter {
+            text: String::from(text.trim()),
+            expression: expression,
+        })
+    }
+
+    pub fn matches(&self, tags: &BTreeSet<String>) -> bool {
+        evaluate(&self.expression, tags)
+    }
+
+    pub fn get_text(&self) -> &str {
+        self.text.as_str()
+    }
+}
+
+pub fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().trim_start_matches('#');
+
+    if tag.is_empty() {
+        return Err(String::from("tags can not be empty"));
+    }
+
+    if tag.chars().any(|c| c.is_whitespace() || SPECIAL_CHARACTERS.contains(&c)) {
+        return Err(format!("tag {} can not contain spaces or any of ( ) ! & |", tag));
+    }
+
+    if is_keyword(tag) {
+        return Err(format!("{} is reserved for filters", tag));
+    }
+
+    Ok(String::from(tag))
+}
+
+fn is_keyword(word: &str) -> bool {
+    match word.to_uppercase().as_str() {
+        "AND" | "OR" | "NOT" => true,
+        _ => false,
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+
+    for c in text.chars().chain(" ".chars()) {
+        if c.is_whitespace() || SPECIAL_CHARACTERS.contains(&c) {
+            if !word.is_empty() {
+                tokens.push(match word.to_uppercase().as_str() {
+                    "AND" => Token::AND,
+                    "OR" => Token::OR,
+                    "NOT" => Token::NOT,
+                    _ => Token::TAG(String::from(word.trim_start_matches('#'))),
+                });
+                word.clear();
+            }
+
+            match c {
+                '(' => tokens.push(Token::OPEN),
+                ')' => tokens.push(Token::CLOSE),
+                '!' => tokens.push(Token::NOT),
+                '&' => tokens.push(Token::AND),
+                '|' => tokens.push(Token::OR),
+                _ => (),
+            }
+        } else {
+            word.push(c);
+        }
+    }
+
+    tokens
+}
+
+fn parse_or(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    let mut expression = parse_and(tokens, position)?;
+
+    while *position < tokens.len() && tokens[*position] == Token::OR {
+        *position += 1;
+        expression = Expression::OR(Box::new(expression), Box::new(parse_and(tokens, position)?));
+    }
+
+    Ok(expression)
+}
+
+fn parse_and(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    let mut expression = parse_not(tokens, position)?;
+
+    while *position < tokens.len() {
+        match tokens[*position] {
+            Token::AND => *position += 1,
+            Token::TAG(_) | Token::NOT | Token::OPEN => (),
+            _ => break,
+        }
+
+        expression = Expression::AND(Box::new(expression), Box::new(parse_not(tokens, position)?));
+    }
+
+    Ok(expression)
+}
+
+fn parse_not(tokens: &Vec<Token>, position: &mut usize) -> Result<Expression, String> {
+    if *position >= tokens.len() {
+        return Err(String::from("the filter ends too early"));
+    }
+
+    *position += 1;
+
+    match tokens[*position - 1] {
+        Token::NOT => Ok(Expression::NOT(Box::new(parse_not(tokens, position)?))),
+        Token::TAG(ref tag) => Ok(Expression::TAG(tag.clone())),
+        Token::OPEN => {
+            let expression = parse_or(tokens, position)?;
+
+            if *position >= tokens.len() || tokens[*position] != Token::CLOSE {
+                return Err(String::from("missing )"));
+            }
+
+            *position += 1;
+            Ok(expression)
+        },
+        Token::CLOSE => Err(String::from("unmatched )")),
+        Token::AND | Token::OR => Err(String::from("AND and OR need a tag on both sides")),
+    }
+}
+
+fn evaluate(expression: &Expression, tags: &BTreeSet<String>) -> bool {
+    match *expression {
+        Expression::TAG(ref tag) => tags.contains(tag),
+        Expression::NOT(ref expression) => !evaluate(expression, tags),
+        Expression::AND(ref left, ref right) => evaluate(left, tags) && evaluate(right, tags),
+        Expression::OR(ref left, ref right) => evaluate(left, tags) || evaluate(right, tags),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(filter: &str, tags: &[&str]) -> bool {
+        let tags = tags.iter().map(|tag| String::from(*tag)).collect();
+        TagFilter::parse(filter).unwrap().matches(&tags)
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert!(matches("NOT a AND b", &["b"]));
+        assert!(!matches("NOT a AND b", &["a", "b"]));
+        assert!(!matches("NOT a AND b", &[]));
+        assert!(matches("!a & b", &["b"]));
+        assert!(matches("NOT (a AND b)", &["a"]));
+        assert!(matches("NOT NOT a", &["a"]));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert!(matches("a OR b AND c", &["a"]));
+        assert!(!matches("a OR b AND c", &["b"]));
+        assert!(matches("a OR b AND c", &["b", "c"]));
+        assert!(!matches("(a OR b) AND c", &["a"]));
+        assert!(matches("a | b c", &["b", "c"]));
+    }
+
+    #[test]
+    fn tags_next_to_each_other_are_anded() {
+        assert!(matches("a b", &["a", "b"]));
+        assert!(!matches("a b", &["a"]));
+        assert!(matches("#a #b", &["a", "b"]));
+        assert!(matches("a and not b", &["a"]));
+    }
+
+    #[test]
+    fn bad_filters_are_errors() {
+        assert!(TagFilter::parse("").is_err());
+        assert!(TagFilter::parse("(a").is_err());
+        assert!(TagFilter::parse("a)").is_err());
+        assert!(TagFilter::parse("a AND").is_err());
+        assert!(TagFilter::parse("OR a").is_err());
+        assert!(TagFilter::parse("NOT").is_err());
+    }
+
+    #[test]
+    fn tag_names() {
+        assert_eq!(normalize_tag(" #todo "), Ok(String::from("todo")));
+        assert!(normalize_tag("#").is_err());
+        assert!(normalize_tag("a b").is_err());
+        assert!(normalize_tag("a|b").is_err());
+        assert!(normalize_tag("Not").is_err());
+    }
+}